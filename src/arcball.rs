@@ -0,0 +1,74 @@
+use crate::quaternion::Quaternion;
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// Maps a point in the `-1` to `1` viewport range onto the half of the unit sphere that faces the camera.
+///
+/// Points outside of the sphere end up on its silhouette.
+pub fn to_sphere(point: &Vector2<f64>) -> Vector3 {
+    let length_squared = point.dot(point);
+    if length_squared <= 1. {
+        // The camera looks down +Z, so the visible half is at -Z
+        Vector3::new(point.x, point.y, -(1. - length_squared).sqrt())
+    } else {
+        Vector3::new(point.x, point.y, 0.).normalized()
+    }
+}
+
+/// Rotation in view space that drags the sphere point under `from` to the one under `to`
+pub fn drag_rotation(from: &Vector2<f64>, to: &Vector2<f64>) -> Quaternion {
+    let from = to_sphere(from);
+    let to = to_sphere(to);
+
+    let axis = from.cross(&to);
+    if axis.length() < f64::EPSILON {
+        return Quaternion::identity();
+    }
+
+    let angle = from.dot(&to).clamp(-1., 1.).acos().to_degrees();
+    Quaternion::from_axis_angle(&axis, angle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length(q: &Quaternion) -> f64 {
+        q.dot(q).sqrt()
+    }
+
+    #[test]
+    fn points_are_on_the_unit_sphere() {
+        for (x, y) in [(0., 0.), (0.5, -0.5), (1., 0.), (2., 3.), (-4., 0.1)] {
+            let point = to_sphere(&Vector2::new(x, y));
+            assert!((point.length() - 1.).abs() < 1e-9);
+            assert!(point.z <= 0.);
+        }
+    }
+
+    #[test]
+    fn drag_gives_a_unit_quaternion() {
+        for (from, to) in [
+            ((0., 0.), (0.5, 0.)),
+            ((-0.3, 0.2), (0.4, -0.6)),
+            ((0.9, 0.9), (-2., 0.5)),
+        ] {
+            let rotation = drag_rotation(&Vector2::new(from.0, from.1), &Vector2::new(to.0, to.1));
+            assert!((length(&rotation) - 1.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn drag_moves_the_point_under_the_cursor() {
+        let (from, to) = (Vector2::new(-0.2, 0.1), Vector2::new(0.3, 0.4));
+        let rotated = drag_rotation(&from, &to).rotate(&to_sphere(&from));
+        assert!((rotated - to_sphere(&to)).length() < 1e-9);
+    }
+
+    #[test]
+    fn no_drag_is_no_rotation() {
+        let point = Vector2::new(0.3, 0.4);
+        let rotation = drag_rotation(&point, &point);
+        assert_eq!(rotation.w, 1.);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::matrix4::Matrix4;
+use crate::vector3::Vector3;
+
+/// Axis-aligned bounding box
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    /// `None` if there are no points
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector3>>(points: I) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |bounds, p| bounds.union_point(p),
+        ))
+    }
+
+    fn union_point(&self, p: &Vector3) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(p.x),
+                self.min.y.min(p.y),
+                self.min.z.min(p.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(p.x),
+                self.max.y.max(p.y),
+                self.max.z.max(p.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, rhs: &Aabb) -> Aabb {
+        self.union_point(&rhs.min).union_point(&rhs.max)
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vector3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vector3::new(min.x, min.y, min.z),
+            Vector3::new(max.x, min.y, min.z),
+            Vector3::new(min.x, max.y, min.z),
+            Vector3::new(max.x, max.y, min.z),
+            Vector3::new(min.x, min.y, max.z),
+            Vector3::new(max.x, min.y, max.z),
+            Vector3::new(min.x, max.y, max.z),
+            Vector3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Box around the transformed box, which can be larger than the transformed contents
+    pub fn transformed(&self, matrix: &Matrix4) -> Aabb {
+        let corners = self.corners().map(|corner| matrix.transform_point(&corner));
+        // There are always eight corners
+        Aabb::from_points(corners.iter()).unwrap_or(*self)
+    }
+
+    /// The sphere that touches the corners
+    pub fn to_sphere(&self) -> BoundingSphere {
+        BoundingSphere {
+            center: self.center(),
+            radius: ((self.max - self.min) * 0.5).length(),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct BoundingSphere {
+    pub center: Vector3,
+    pub radius: f64,
+}
@@ -0,0 +1,73 @@
+use crate::frustum::Frustum;
+use crate::matrix4::Matrix4;
+use crate::quaternion::Quaternion;
+use crate::terminal_renderer::DrawBuffer;
+use crate::transform::Transform;
+use crate::vector3::Vector3;
+
+/// A perspective camera looking down its local +Z axis, with +X to the right and +Y downwards
+pub struct Camera {
+    pub position: Vector3,
+    /// Same convention as `Vector3::rotate_euler`
+    pub euler_angles: Vector3,
+    /// Vertical field of view in degrees
+    pub fov: f64,
+    /// Width divided by height
+    pub aspect_ratio: f64,
+    pub near: f64,
+    pub far: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vector3::new(0., 0., -3.),
+            euler_angles: Vector3::zero(),
+            fov: 40.,
+            aspect_ratio: 1.,
+            near: 0.1,
+            far: 100.,
+        }
+    }
+}
+
+impl Camera {
+    /// The pixels of the draw buffer aren't always square, this uses the shape on the terminal
+    pub fn update_aspect_ratio(&mut self, draw_buffer: &DrawBuffer) {
+        self.aspect_ratio = draw_buffer.aspect_ratio();
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            position: self.position,
+            rotation: Quaternion::from_euler(&self.euler_angles),
+            scale: Vector3::one(),
+        }
+    }
+
+    /// Transforms from world space into the camera's local space
+    pub fn view_matrix(&self) -> Matrix4 {
+        self.transform().to_inverse_matrix()
+    }
+
+    /// Transforms from the camera's local space to normalized device coordinates
+    pub fn projection_matrix(&self) -> Matrix4 {
+        Matrix4::perspective(self.fov, self.aspect_ratio, self.near, self.far)
+    }
+
+    pub fn view_projection_matrix(&self) -> Matrix4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(&self.view_projection_matrix())
+    }
+
+    /// Projects a point from world space to normalized device coordinates.
+    ///
+    /// `x` and `y` are in the `-1` to `1` range, with `(0,0)` at the center.
+    /// `z` is `0` at the near plane and `1` at the far plane.
+    pub fn project(&self, point: &Vector3) -> Vector3 {
+        self.view_projection_matrix().transform_point(point)
+    }
+}
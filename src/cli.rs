@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+
+use rust_cube::material::Shading;
+use rust_cube::render_context::RenderMode;
+use rust_cube::terminal_renderer::{CellMode, ColorDepth};
+use rust_cube::vector3::Vector3;
+
+pub const USAGE: &str = "\
+Renders 3D models in the terminal
+
+USAGE:
+    rust-cube [OPTIONS] [FILE]
+
+ARGS:
+    <FILE>    Model (.obj, .gltf, .glb, .stl, .ply) or scene (.json) to show,
+              a textured cube if there is none
+
+OPTIONS:
+    -t, --texture <FILE>       Texture for the cube, or for every material of the model or scene
+    -f, --fps <NUMBER>         Updates per second [default: 10]
+    -m, --mode <MODE>          textured, uv or depth [default: textured]
+    -c, --colors <DEPTH>       truecolor, 256 or 16 [default: truecolor]
+        --cells <CELLS>        half (half blocks), quadrant (2x2 pixels), braille (2x4 dots)
+                               or underscore (for terminals without half blocks)
+                               [default: half]
+        --shading <SHADING>    flat, gouraud or phong for every lit material,
+                               instead of the shading of the materials
+    -r, --rotation <X,Y,Z>     Initial rotation in degrees [default: 0,0,0]
+    -s, --spin <X,Y,Z>         Rotation in degrees per second [default: 0,0,0]
+    -o, --output <FILE>        Renders a single frame to a file instead of the terminal,
+                               as an image if it ends with .png and as ANSI text otherwise
+        --size <WIDTHxHEIGHT>  Size of the output file in terminal cells [default: 80x24]
+    -h, --help                 Prints this message
+
+KEYS:
+    L                          Load an image as the texture
+    S                          Cycle through flat, gouraud and phong shading
+    B                          Cycle through half blocks, quadrants, braille and underscores
+    D                          Show how many objects are culled
+    Esc, Q                     Quit
+";
+
+pub struct Options {
+    pub file: Option<PathBuf>,
+    pub texture: Option<PathBuf>,
+    pub fps: u32,
+    pub mode: RenderMode,
+    pub color_depth: ColorDepth,
+    pub cell_mode: CellMode,
+    pub shading: Option<Shading>,
+    pub rotation: Vector3,
+    pub spin: Vector3,
+    pub output: Option<PathBuf>,
+    /// `(columns, rows)`
+    pub size: (u16, u16),
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            file: None,
+            texture: None,
+            fps: 10,
+            mode: RenderMode::Textured,
+            color_depth: ColorDepth::TrueColor,
+            cell_mode: CellMode::HalfBlock,
+            shading: None,
+            rotation: Vector3::zero(),
+            spin: Vector3::zero(),
+            output: None,
+            size: (80, 24),
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the arguments without the program name, the error is meant for the user
+    pub fn parse<I: Iterator<Item = String>>(mut arguments: I) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        while let Some(argument) = arguments.next() {
+            // Both "--fps 30" and "--fps=30" work
+            let (name, inline_value) = match argument.split_once('=') {
+                Some((name, value)) if argument.starts_with("--") => {
+                    (name.to_string(), Some(value.to_string()))
+                }
+                _ => (argument.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| arguments.next())
+                    .ok_or_else(|| format!("missing value for '{}'", name))
+            };
+
+            match name.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-t" | "--texture" => options.texture = Some(PathBuf::from(value()?)),
+                "-f" | "--fps" => {
+                    let fps = value()?;
+                    options.fps = match fps.parse() {
+                        Ok(fps) if fps > 0 => fps,
+                        _ => return Err(format!("'{}' is not a valid number of updates", fps)),
+                    };
+                }
+                "-m" | "--mode" => {
+                    options.mode = match value()?.as_str() {
+                        "textured" => RenderMode::Textured,
+                        "uv" => RenderMode::Uv,
+                        "depth" => RenderMode::Depth,
+                        mode => return Err(format!("unknown render mode '{}'", mode)),
+                    };
+                }
+                "-c" | "--colors" => {
+                    options.color_depth = match value()?.as_str() {
+                        "truecolor" | "24bit" => ColorDepth::TrueColor,
+                        "256" => ColorDepth::Ansi256,
+                        "16" => ColorDepth::Ansi16,
+                        depth => return Err(format!("unknown color depth '{}'", depth)),
+                    };
+                }
+                "--cells" => {
+                    options.cell_mode = match value()?.as_str() {
+                        "half" => CellMode::HalfBlock,
+                        "underscore" => CellMode::Underscore,
+                        "quadrant" => CellMode::Quadrant,
+                        "braille" => CellMode::Braille,
+                        cells => return Err(format!("unknown cell mode '{}'", cells)),
+                    };
+                }
+                "--shading" => {
+                    options.shading = match value()?.as_str() {
+                        "flat" => Some(Shading::Flat),
+                        "gouraud" => Some(Shading::Gouraud),
+                        "phong" => Some(Shading::Phong),
+                        shading => return Err(format!("unknown shading '{}'", shading)),
+                    };
+                }
+                "-r" | "--rotation" => options.rotation = parse_vector3(&value()?)?,
+                "-s" | "--spin" => options.spin = parse_vector3(&value()?)?,
+                "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+                "--size" => options.size = parse_size(&value()?)?,
+                _ if name.starts_with('-') && name.len() > 1 => {
+                    return Err(format!("unknown option '{}'", name))
+                }
+                _ if options.file.is_none() => options.file = Some(PathBuf::from(argument)),
+                _ => return Err(format!("unexpected argument '{}'", argument)),
+            }
+        }
+
+        // After the loop, `--cells` can come after `--size`
+        let (columns, rows) = options.size;
+        if options.cell_mode.pixel_size(columns, rows).is_none() {
+            return Err(format!(
+                "size {}x{} is too large for the cell mode",
+                columns, rows
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+/// `x,y,z`
+fn parse_vector3(text: &str) -> Result<Vector3, String> {
+    let values = text
+        .split(',')
+        .map(|value| value.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>();
+    match values.as_deref() {
+        Ok([x, y, z]) => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(format!("'{}' is not a vector like 0,45,0", text)),
+    }
+}
+
+/// `widthxheight`
+fn parse_size(text: &str) -> Result<(u16, u16), String> {
+    let size = text
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("'{}' is not a size like 80x24", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(arguments: &[&str]) -> Result<Options, String> {
+        Options::parse(arguments.iter().map(|argument| argument.to_string()))
+    }
+
+    fn parse_ok(arguments: &[&str]) -> Options {
+        parse(arguments).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let options = parse_ok(&[]);
+        assert!(options.file.is_none());
+        assert_eq!(options.fps, 10);
+        assert!(options.mode == RenderMode::Textured);
+        assert!(options.color_depth == ColorDepth::TrueColor);
+        assert!(options.shading.is_none());
+        assert!(options.output.is_none());
+        assert_eq!(options.size, (80, 24));
+        assert!(!options.help);
+    }
+
+    #[test]
+    fn options_with_separate_and_inline_values() {
+        let options = parse_ok(&[
+            "model.obj",
+            "--fps=30",
+            "-m",
+            "depth",
+            "-c",
+            "256",
+            "--shading",
+            "phong",
+            "-s",
+            "0, 45,0",
+            "-o",
+            "frame.png",
+            "--size=100x30",
+        ]);
+        assert_eq!(options.file, Some(PathBuf::from("model.obj")));
+        assert_eq!(options.fps, 30);
+        assert!(options.mode == RenderMode::Depth);
+        assert!(options.color_depth == ColorDepth::Ansi256);
+        assert!(options.shading == Some(Shading::Phong));
+        assert_eq!(
+            (options.spin.x, options.spin.y, options.spin.z),
+            (0., 45., 0.)
+        );
+        assert_eq!(options.output, Some(PathBuf::from("frame.png")));
+        assert_eq!(options.size, (100, 30));
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        for arguments in [
+            &["--unknown"][..],
+            &["--fps"],
+            &["--fps", "0"],
+            &["-m", "wireframe"],
+            &["-c", "8"],
+            &["-r", "1,2"],
+            &["a.obj", "b.obj"],
+            &["--size", "20000x20000", "--cells", "braille"],
+            &["--cells", "quadrant", "--size=40000x10"],
+        ] {
+            assert!(parse(arguments).is_err(), "{:?}", arguments);
+        }
+    }
+
+    #[test]
+    fn size_fits_the_half_blocks() {
+        let options = parse_ok(&["--size", "40000x10"]);
+        assert_eq!(options.size, (40000, 10));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("80x24"), Ok((80, 24)));
+        for size in ["80", "x24", "0x24", "80x0", "-1x24", "80x24x2", "70000x24"] {
+            assert!(parse_size(size).is_err(), "{}", size);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::vector4::Vector4;
+
+/// Corner of a clipped triangle
+#[derive(Copy, Clone)]
+pub struct ClipVertex {
+    /// In homogeneous clip space, before the perspective divide
+    pub position: Vector4,
+    /// Weights of the vertices of the original triangle, for interpolating their attributes
+    pub weights: [f64; 3],
+}
+
+impl ClipVertex {
+    fn lerp(&self, rhs: &ClipVertex, t: f64) -> ClipVertex {
+        ClipVertex {
+            position: self.position + (rhs.position - self.position) * t,
+            weights: [0, 1, 2].map(|i| self.weights[i] + (rhs.weights[i] - self.weights[i]) * t),
+        }
+    }
+}
+
+/// Near, far, left, right, top and bottom
+const PLANE_COUNT: usize = 6;
+
+/// Signed distance to a plane of the view frustum, points with a negative distance are outside.
+///
+/// The projection maps the frustum to `-w` to `w` on X and Y, and `0` to `w` on Z.
+fn distance(plane: usize, v: &Vector4) -> f64 {
+    match plane {
+        0 => v.z,
+        1 => v.w - v.z,
+        2 => v.w + v.x,
+        3 => v.w - v.x,
+        4 => v.w + v.y,
+        _ => v.w - v.y,
+    }
+}
+
+/// Sutherland–Hodgman clipping against the view frustum, the vertices are in clip space.
+///
+/// Returns the corners of a convex polygon, which is empty if the triangle is outside of the view.
+/// Everything in it is in front of the camera and on the screen, so it is safe to divide by `w`.
+pub fn clip_triangle(vertices: &[Vector4; 3]) -> Vec<ClipVertex> {
+    let mut polygon = vec![
+        ClipVertex {
+            position: vertices[0],
+            weights: [1., 0., 0.],
+        },
+        ClipVertex {
+            position: vertices[1],
+            weights: [0., 1., 0.],
+        },
+        ClipVertex {
+            position: vertices[2],
+            weights: [0., 0., 1.],
+        },
+    ];
+
+    for plane in 0..PLANE_COUNT {
+        // Most triangles are completely inside
+        if polygon
+            .iter()
+            .all(|vertex| distance(plane, &vertex.position) >= 0.)
+        {
+            continue;
+        }
+
+        let input = std::mem::take(&mut polygon);
+        for (index, current) in input.iter().enumerate() {
+            let next = &input[(index + 1) % input.len()];
+            let current_distance = distance(plane, &current.position);
+            let next_distance = distance(plane, &next.position);
+
+            if current_distance >= 0. {
+                polygon.push(*current);
+            }
+            // The edge crosses the plane
+            if (current_distance >= 0.) != (next_distance >= 0.) {
+                let t = current_distance / (current_distance - next_distance);
+                polygon.push(current.lerp(next, t));
+            }
+        }
+
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn is_in_view(v: &Vector4) -> bool {
+        (0..PLANE_COUNT).all(|plane| distance(plane, v) >= -EPSILON)
+    }
+
+    #[test]
+    fn inside_triangle_is_unchanged() {
+        let vertices = [
+            Vector4::new(-0.5, -0.5, 0.5, 1.),
+            Vector4::new(0.5, -0.5, 0.5, 1.),
+            Vector4::new(0., 0.5, 0.5, 1.),
+        ];
+        let polygon = clip_triangle(&vertices);
+        assert_eq!(polygon.len(), 3);
+        for (index, vertex) in polygon.iter().enumerate() {
+            assert_eq!(vertex.position.x, vertices[index].x);
+            assert_eq!(vertex.position.y, vertices[index].y);
+            let mut weights = [0.; 3];
+            weights[index] = 1.;
+            assert_eq!(vertex.weights, weights);
+        }
+    }
+
+    #[test]
+    fn outside_triangles_are_removed() {
+        // Right of the view
+        let right = [
+            Vector4::new(2., 0., 0.5, 1.),
+            Vector4::new(3., 0., 0.5, 1.),
+            Vector4::new(2., 0.5, 0.5, 1.),
+        ];
+        assert!(clip_triangle(&right).is_empty());
+
+        // Behind the camera
+        let behind = [
+            Vector4::new(0., 0., -1., 1.),
+            Vector4::new(0.5, 0., -1., 1.),
+            Vector4::new(0., 0.5, -1., 1.),
+        ];
+        assert!(clip_triangle(&behind).is_empty());
+    }
+
+    #[test]
+    fn partially_visible_triangle_is_clipped() {
+        let vertices = [
+            Vector4::new(0., 0., 0.5, 1.),
+            Vector4::new(3., 0., 0.5, 1.),
+            Vector4::new(0., 0.5, -0.5, 1.),
+        ];
+        let polygon = clip_triangle(&vertices);
+        assert!(polygon.len() >= 3);
+        for vertex in &polygon {
+            assert!(is_in_view(&vertex.position));
+            assert!((vertex.weights.iter().sum::<f64>() - 1.).abs() < EPSILON);
+
+            // The weights reproduce the position
+            let interpolated = vertices[0] * vertex.weights[0]
+                + vertices[1] * vertex.weights[1]
+                + vertices[2] * vertex.weights[2];
+            assert!((interpolated.x - vertex.position.x).abs() < EPSILON);
+            assert!((interpolated.y - vertex.position.y).abs() < EPSILON);
+            assert!((interpolated.z - vertex.position.z).abs() < EPSILON);
+        }
+    }
+}
@@ -1,6 +1,11 @@
-use image::{DynamicImage, GenericImageView};
-
+use crate::bounds::Aabb;
+use crate::material::Material;
+use crate::matrix4::Matrix4;
+use crate::mesh::{Mesh, MeshGroup};
+use crate::quaternion::Quaternion;
+use crate::render_context::RenderContext;
 use crate::terminal_renderer::{DrawBuffer, RgbColor};
+use crate::transform::Transform;
 use crate::vector2::Vector2;
 use crate::vector3::Vector3;
 
@@ -8,6 +13,74 @@ pub struct Cube {
     pub pos: Vector3,
     pub size: Vector3,
     pub euler_angles: Vector3,
+    pub materials: CubeMaterials,
+}
+
+pub enum CubeMaterials {
+    /// Same order as `Cube::get_faces`
+    PerFace([Material; 6]),
+    /// A single texture with all six faces unfolded onto it
+    Atlas(Material, AtlasLayout),
+}
+
+/// Where each face of the cube is found in an atlas texture
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum AtlasLayout {
+    /// Three columns and two rows, with the faces in the same order as `Cube::get_faces`
+    Grid3x2,
+    /// Four columns and three rows, like an unfolded box, with the indices of `Cube::get_faces`
+    /// ```text
+    ///         5 top
+    /// 2 left  3 front  4 right  1 back
+    ///         0 bottom
+    /// ```
+    Cross,
+}
+
+impl CubeMaterials {
+    /// The same material on every face
+    pub fn uniform(material: Material) -> CubeMaterials {
+        CubeMaterials::PerFace([(); 6].map(|_| material.clone()))
+    }
+
+    pub fn as_slice(&self) -> &[Material] {
+        match self {
+            CubeMaterials::PerFace(materials) => materials,
+            CubeMaterials::Atlas(material, _) => std::slice::from_ref(material),
+        }
+    }
+
+    /// Material and texture coordinates of a face, the coordinates are in the same order as the `Rectangle3D` corners
+    pub fn get_face(&self, index: usize) -> (&Material, [Vector2<f64>; 4]) {
+        match self {
+            CubeMaterials::PerFace(materials) => (&materials[index], Rectangle3D::FULL_UVS),
+            CubeMaterials::Atlas(material, layout) => (material, layout.get_face_uvs(index)),
+        }
+    }
+}
+
+impl AtlasLayout {
+    pub fn get_face_uvs(&self, index: usize) -> [Vector2<f64>; 4] {
+        let (columns, rows, (column, row), flipped) = match self {
+            AtlasLayout::Grid3x2 => (3., 2., (index % 3, index / 3), false),
+            AtlasLayout::Cross => {
+                // The top and bottom faces are upside down compared to the sides
+                let cell = [(1, 2), (3, 1), (0, 1), (1, 1), (2, 1), (1, 0)][index];
+                (4., 3., cell, index == 0 || index == 5)
+            }
+        };
+
+        let min = Vector2::new(column as f64 / columns, row as f64 / rows);
+        let max = Vector2::new((column + 1) as f64 / columns, (row + 1) as f64 / rows);
+        Rectangle3D::FULL_UVS.map(|uv| {
+            let uv = if flipped {
+                Vector2::new(1. - uv.x, 1. - uv.y)
+            } else {
+                uv
+            };
+            min + (max - min) * uv
+        })
+    }
 }
 
 impl Cube {
@@ -16,24 +89,33 @@ impl Cube {
             pos: Vector3::zero(),
             size: Vector3::new(1., 1., 1.),
             euler_angles: Vector3::zero(),
+            materials: CubeMaterials::PerFace(RAINBOW.map(Material::from_color)),
         }
     }
 
-    /// `(top, sides, bottom)`
+    pub fn transform(&self) -> Transform {
+        Transform {
+            position: self.pos,
+            rotation: Quaternion::from_euler(&self.euler_angles),
+            scale: self.size,
+        }
+    }
+
+    /// `(y = 1, sides, y = -1)`, so the first face is the bottom one since +Y points down
     pub fn get_faces(&self) -> [Rectangle3D; 6] {
-        let a = self.pos + (self.size * Vector3::new(1., 1., 1.)).rotate_euler(&self.euler_angles);
-        let b = self.pos + (self.size * Vector3::new(-1., 1., 1.)).rotate_euler(&self.euler_angles);
-        let c =
-            self.pos + (self.size * Vector3::new(-1., 1., -1.)).rotate_euler(&self.euler_angles);
-        let d = self.pos + (self.size * Vector3::new(1., 1., -1.)).rotate_euler(&self.euler_angles);
+        Cube::get_transformed_faces(&self.transform().to_matrix())
+    }
 
-        let e = self.pos + (self.size * Vector3::new(1., -1., 1.)).rotate_euler(&self.euler_angles);
-        let f =
-            self.pos + (self.size * Vector3::new(-1., -1., 1.)).rotate_euler(&self.euler_angles);
-        let g =
-            self.pos + (self.size * Vector3::new(-1., -1., -1.)).rotate_euler(&self.euler_angles);
-        let h =
-            self.pos + (self.size * Vector3::new(1., -1., -1.)).rotate_euler(&self.euler_angles);
+    fn get_transformed_faces(model: &Matrix4) -> [Rectangle3D; 6] {
+        let a = model.transform_point(&Vector3::new(1., 1., 1.));
+        let b = model.transform_point(&Vector3::new(-1., 1., 1.));
+        let c = model.transform_point(&Vector3::new(-1., 1., -1.));
+        let d = model.transform_point(&Vector3::new(1., 1., -1.));
+
+        let e = model.transform_point(&Vector3::new(1., -1., 1.));
+        let f = model.transform_point(&Vector3::new(-1., -1., 1.));
+        let g = model.transform_point(&Vector3::new(-1., -1., -1.));
+        let h = model.transform_point(&Vector3::new(1., -1., -1.));
 
         let faces: [Rectangle3D; 6] = [
             Rectangle3D {
@@ -77,19 +159,71 @@ impl Cube {
         faces
     }
 
-    pub fn render(&self, draw_buffer: &mut DrawBuffer, image: &DynamicImage) {
-        let faces = self.get_faces();
+    /// In local space, without the position, size and rotation.
+    /// Has one group per face, which refer to the materials in `CubeMaterials::as_slice`.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::empty();
 
-        let forward = Vector3::new(0., 0., 1.);
-        for (index, face) in faces.iter().enumerate() {
-            let normal = face.scaled_normal();
-            // Backface culling
-            if forward.dot(&normal) > 0. {
-                continue;
-            }
+        for (index, face) in Cube::get_transformed_faces(&Matrix4::identity())
+            .iter()
+            .enumerate()
+        {
+            let (_, uvs) = self.materials.get_face(index);
+            let start = mesh.positions.len() as u32;
+            mesh.positions.extend_from_slice(&[
+                face.top_left,
+                face.top_right,
+                face.bottom_right,
+                face.bottom_left,
+            ]);
+            // `scaled_normal` points into the cube
+            let normal = (face.scaled_normal() * -1.).normalized();
+            mesh.normals.extend_from_slice(&[normal; 4]);
+            mesh.uvs.extend_from_slice(&uvs);
+
+            // Two triangles, split along the diagonal from the top left to the bottom right corner
+            mesh.groups.push(MeshGroup {
+                start: mesh.indices.len(),
+                count: 6,
+                material: match self.materials {
+                    CubeMaterials::PerFace(_) => index,
+                    CubeMaterials::Atlas(_, _) => 0,
+                },
+            });
+            mesh.indices.extend_from_slice(&[
+                start,
+                start + 1,
+                start + 2,
+                start,
+                start + 2,
+                start + 3,
+            ]);
+        }
 
-            face.render(draw_buffer, index, &image);
+        mesh
+    }
+
+    /// In world space
+    pub fn bounds(&self) -> Aabb {
+        let unit = Aabb {
+            min: Vector3::new(-1., -1., -1.),
+            max: Vector3::new(1., 1., 1.),
+        };
+        unit.transformed(&self.transform().to_matrix())
+    }
+
+    /// Skipped if the cube is outside of the view
+    pub fn render(&self, draw_buffer: &mut DrawBuffer, context: &RenderContext) {
+        if !context.camera.frustum().intersects(&self.bounds()) {
+            return;
         }
+
+        self.to_mesh().render(
+            draw_buffer,
+            context,
+            &self.transform().to_matrix(),
+            self.materials.as_slice(),
+        );
     }
 }
 
@@ -122,181 +256,15 @@ const RAINBOW: [RgbColor; 6] = [
 ];
 
 impl Rectangle3D {
+    /// The whole texture, in the same order as the corners
+    pub const FULL_UVS: [Vector2<f64>; 4] = [
+        Vector2 { x: 0., y: 1. },
+        Vector2 { x: 1., y: 1. },
+        Vector2 { x: 1., y: 0. },
+        Vector2 { x: 0., y: 0. },
+    ];
+
     pub fn scaled_normal(&self) -> Vector3 {
         (self.bottom_left - self.top_left).cross(&(self.top_right - self.top_left))
     }
-
-    pub fn render(&self, draw_buffer: &mut DrawBuffer, index: usize, image: &DynamicImage) {
-        let horizontal = self.top_right - self.top_left;
-        let vertical = self.bottom_left - self.top_left;
-
-        // Find bounding box
-        let mut bounding_box_min = Vector2::new(self.top_left.x, self.top_left.y);
-        let mut bounding_box_max = Vector2::new(self.top_left.x, self.top_left.y);
-        for point in [self.top_right, self.bottom_right, self.bottom_left] {
-            let point = Vector2::new(point.x, point.y);
-
-            bounding_box_min = bounding_box_min.min(&point);
-            bounding_box_max = bounding_box_max.max(&point);
-        }
-
-        // Viewport: -1 to 1 range, with (0,0) at the center
-        // Transform to draw buffer size
-        let half_size = 0.5 * (draw_buffer.width.min(draw_buffer.height) as f64);
-        let half_width = (draw_buffer.width / 2) as f64;
-        let half_height = (draw_buffer.height / 2) as f64;
-        let project = |v: Vector2<f64>| {
-            (
-                (v.x * half_size + half_width) as u16,
-                (v.y * half_size + half_height) as u16,
-            )
-        };
-        let unproject = |v: (u16, u16)| Vector2 {
-            x: (v.0 as f64 - half_width) / half_size,
-            y: (v.1 as f64 - half_height) / half_size,
-        };
-
-        // Transformed coordinates
-        let bounding_box_min_2d = project(bounding_box_min);
-        let bounding_box_max_2d = project(bounding_box_max);
-
-        let top_left_2d: Vector2<f64> = self.top_left.into();
-        let top_right_2d: Vector2<f64> = self.top_right.into();
-        let bottom_right_2d: Vector2<f64> = self.bottom_right.into();
-        let bottom_left_2d: Vector2<f64> = self.bottom_left.into();
-
-        // Reference: A Quadrilateral Rendering Primitive https://dl.acm.org/doi/10.1145/1058129.1058131
-        let bary = |point: &Vector2<f64>| {
-            let s =
-                [bottom_left_2d, bottom_right_2d, top_right_2d, top_left_2d].map(|v| v - *point);
-            let mut A: [f64; 4] = [0., 0., 0., 0.];
-            for (index, element) in s.iter().enumerate() {
-                let next = s[(index + 1) % s.len()];
-                A[index] = element.wedge_product(&next);
-            }
-
-            let mut D: [f64; 4] = [0., 0., 0., 0.];
-            for (index, element) in s.iter().enumerate() {
-                let next = s[(index + 1) % s.len()];
-                D[index] = element.dot(&next);
-            }
-
-            let r = s.map(|v| v.length() * 1.); // w_i goes here, not sure what that is
-
-            let mut t: [f64; 4] = [0., 0., 0., 0.];
-            for (index, element) in r.iter().enumerate() {
-                let next = r[(index + 1) % r.len()];
-                t[index] = (element * next - D[index]) / A[index];
-            }
-
-            let mut mu: [f64; 4] = [0., 0., 0., 0.];
-            for (index, element) in t.iter().enumerate() {
-                let prev = t[(index as isize - 1).rem_euclid(t.len() as isize) as usize];
-                mu[index] = (prev * element) / r[index];
-            }
-
-            let sum = mu[0] + mu[1] + mu[2] + mu[3];
-
-            [mu[0] / sum, mu[1] / sum, mu[2] / sum, mu[3] / sum]
-        };
-
-        let horizontal_2d = Vector2::new(horizontal.x, horizontal.y);
-        let vertical_2d = Vector2::new(vertical.x, vertical.y);
-
-        let horizontal_2d_direction = horizontal_2d.normalized();
-        let vertical_2d_direction = vertical_2d.normalized();
-
-        let horizontal_2d_length = horizontal_2d.length();
-        let vertical_2d_length = vertical_2d.length();
-
-        // Iterate over draw buffer
-        for column in bounding_box_min_2d.0..=bounding_box_max_2d.0 {
-            for row in bounding_box_min_2d.1..=bounding_box_max_2d.1 {
-                let point = unproject((column, row));
-
-                let barycentric_coordinates = bary(&point);
-                let uv = Vector2::new(0., 0.)
-                    * Vector2::new(barycentric_coordinates[0], barycentric_coordinates[0])
-                    + Vector2::new(1., 0.)
-                        * Vector2::new(barycentric_coordinates[1], barycentric_coordinates[1])
-                    + Vector2::new(1., 1.)
-                        * Vector2::new(barycentric_coordinates[2], barycentric_coordinates[2])
-                    + Vector2::new(0., 1.)
-                        * Vector2::new(barycentric_coordinates[3], barycentric_coordinates[3]);
-
-                let b = (point - top_left_2d).wedge_product(&(top_right_2d - top_left_2d)) >= 0.
-                    && (point - top_right_2d).wedge_product(&(bottom_right_2d - top_right_2d))
-                        >= 0.
-                    && (point - bottom_right_2d).wedge_product(&(bottom_left_2d - bottom_right_2d))
-                        >= 0.
-                    && (point - bottom_left_2d).wedge_product(&(top_left_2d - bottom_left_2d))
-                        >= 0.;
-                if b {
-                    let col = &RAINBOW[index % RAINBOW.len()];
-
-                    draw_buffer.set_color(
-                        column,
-                        row,
-                        &RgbColor {
-                            r: col.r / 2,
-                            g: col.g / 2,
-                            b: col.b / 2,
-                        },
-                    );
-
-                    // TODO: Wrap around or something?
-                    let pixel = image.get_pixel(
-                        (uv.x * image.width() as f64) as u32,
-                        (uv.y * image.height() as f64) as u32,
-                    );
-
-                    draw_buffer.set_color(
-                        column,
-                        row,
-                        &RgbColor {
-                            r: pixel.0[0],
-                            g: pixel.0[1],
-                            b: pixel.0[2],
-                        },
-                    )
-
-                    /*
-                    if barycentric_coordinates[0] > 0.5 {
-                        draw_buffer.set_color(
-                            column,
-                            row,
-                            &RgbColor {
-                                r: 255,
-                                g: 255,
-                                b: 255,
-                            },
-                        )
-                    }*/
-
-                    // Draw the bary coords
-                    /*draw_buffer.set_color(
-                        column,
-                        row,
-                        &RgbColor {
-                            r: (uv.x * 255.) as u8,
-                            g: (uv.y * 255.) as u8,
-                            b: 0,
-                        },
-                    )*/
-                }
-            }
-        }
-
-        {
-            let tl = project(Vector2::new(self.top_left.x, self.top_left.y));
-            let tr = project(Vector2::new(self.top_right.x, self.top_right.y));
-            let bl = project(Vector2::new(self.bottom_left.x, self.bottom_left.y));
-            let br = project(Vector2::new(self.bottom_right.x, self.bottom_right.y));
-            draw_buffer.set_color(tl.0, tl.1, &RAINBOW[index % RAINBOW.len()]);
-            draw_buffer.set_color(tr.0, tr.1, &RAINBOW[index % RAINBOW.len()]);
-            draw_buffer.set_color(bl.0, bl.1, &RAINBOW[index % RAINBOW.len()]);
-            draw_buffer.set_color(br.0, br.1, &RAINBOW[index % RAINBOW.len()]);
-        }
-        return;
-    }
 }
@@ -0,0 +1,161 @@
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::matrix4::Matrix4;
+use crate::vector3::Vector3;
+
+/// Plane through the points where `normal.dot(p) + distance` is `0`, the normal points into the frustum
+#[derive(Copy, Clone)]
+struct Plane {
+    normal: Vector3,
+    distance: f64,
+}
+
+impl Plane {
+    /// Normalized, so that `signed_distance` is in world units
+    fn new(a: f64, b: f64, c: f64, d: f64) -> Plane {
+        let length = Vector3::new(a, b, c).length().max(f64::EPSILON);
+        Plane {
+            normal: Vector3::new(a / length, b / length, c / length),
+            distance: d / length,
+        }
+    }
+
+    fn signed_distance(&self, p: &Vector3) -> f64 {
+        self.normal.dot(p) + self.distance
+    }
+}
+
+/// The part of the world that the camera sees, in world space
+pub struct Frustum {
+    /// Near, far, left, right, top and bottom
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the rows of the matrix (Gribb and Hartmann),
+    /// for the same clip space as `clipping::clip_triangle`
+    pub fn from_view_projection(matrix: &Matrix4) -> Frustum {
+        let m = &matrix.m;
+        // `w + sign * row`, like the distances in `clipping`
+        let plane = |row: &[f64; 4], sign: f64| {
+            let w = &m[3];
+            Plane::new(
+                w[0] + sign * row[0],
+                w[1] + sign * row[1],
+                w[2] + sign * row[2],
+                w[3] + sign * row[3],
+            )
+        };
+
+        Frustum {
+            planes: [
+                Plane::new(m[2][0], m[2][1], m[2][2], m[2][3]),
+                plane(&m[2], -1.),
+                plane(&m[0], 1.),
+                plane(&m[0], -1.),
+                plane(&m[1], 1.),
+                plane(&m[1], -1.),
+            ],
+        }
+    }
+
+    /// `false` if the sphere is completely outside, cheaper but less exact than `intersects_aabb`
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(&sphere.center) >= -sphere.radius)
+    }
+
+    /// Tries the sphere around the box first, most objects are either far outside or inside
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.intersects_sphere(&aabb.to_sphere()) && self.intersects_aabb(aabb)
+    }
+
+    /// `false` if the box is completely outside of one of the planes.
+    /// Boxes near the corners of the frustum can be outside without being detected.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner that is the furthest inside
+            let corner = Vector3::new(
+                if plane.normal.x >= 0. {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0. {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0. {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.signed_distance(&corner) >= 0.
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    /// Cube with the given center and half size
+    fn cube(x: f64, y: f64, z: f64, size: f64) -> Aabb {
+        Aabb {
+            min: Vector3::new(x - size, y - size, z - size),
+            max: Vector3::new(x + size, y + size, z + size),
+        }
+    }
+
+    /// The default camera is at `(0, 0, -3)` and looks along Z
+    fn frustum() -> Frustum {
+        Camera::default().frustum()
+    }
+
+    #[test]
+    fn box_in_front_is_visible() {
+        assert!(frustum().intersects(&cube(0., 0., 0., 1.)));
+        assert!(frustum().intersects(&cube(0., 0., 50., 0.1)));
+    }
+
+    #[test]
+    fn boxes_outside_are_culled() {
+        let frustum = frustum();
+        // Behind the camera
+        assert!(!frustum.intersects(&cube(0., 0., -10., 1.)));
+        // Beyond the far plane
+        assert!(!frustum.intersects(&cube(0., 0., 200., 1.)));
+        // Far to the sides
+        assert!(!frustum.intersects(&cube(-20., 0., 0., 1.)));
+        assert!(!frustum.intersects(&cube(20., 0., 0., 1.)));
+        assert!(!frustum.intersects(&cube(0., -20., 0., 1.)));
+        assert!(!frustum.intersects(&cube(0., 20., 0., 1.)));
+    }
+
+    #[test]
+    fn straddling_boxes_are_visible() {
+        let frustum = frustum();
+        // Around the camera
+        assert!(frustum.intersects(&cube(0., 0., -3., 1.)));
+        // Across the left edge of the view
+        assert!(frustum.intersects(&cube(-1.5, 0., 0., 1.)));
+        // Across the far plane
+        assert!(frustum.intersects(&cube(0., 0., 97., 1.)));
+    }
+
+    #[test]
+    fn spheres() {
+        let frustum = frustum();
+        let sphere = |x: f64, z: f64| BoundingSphere {
+            center: Vector3::new(x, 0., z),
+            radius: 1.,
+        };
+        assert!(frustum.intersects_sphere(&sphere(0., 0.)));
+        assert!(frustum.intersects_sphere(&sphere(0., -3.5)));
+        assert!(!frustum.intersects_sphere(&sphere(0., -5.)));
+        assert!(!frustum.intersects_sphere(&sphere(20., 0.)));
+    }
+}
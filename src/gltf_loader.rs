@@ -0,0 +1,562 @@
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use gltf::{buffer, image as gltf_image, mesh, Document, Gltf};
+
+use crate::material::{CullMode, Material, Shading};
+use crate::mesh::{Mesh, MeshGroup};
+use crate::model::{LoadError, Model};
+use crate::quaternion::Quaternion;
+use crate::scene::{Node, Scene};
+use crate::terminal_renderer::RgbColor;
+use crate::texture::{AddressMode, FilterMode, MipmapMode, Sampler, Texture};
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// Loads a `.gltf` or `.glb` file, including external and embedded buffers and images.
+///
+/// Returns a node without a transform, with one child for every root node of the default scene.
+/// The glTF nodes keep their names and transforms, and the nodes that use the same mesh share its model.
+/// glTF is Y-up with the viewer looking down -Z, so the Y and Z axes get flipped.
+pub fn load_scene(path: &Path) -> Result<Node, LoadError> {
+    Ok(read(path)?.0)
+}
+
+/// Like `load_scene`, but flattens the node hierarchy into a single model
+pub fn load(path: &Path) -> Result<Model, LoadError> {
+    let (root, materials) = read(path)?;
+    let scene = Scene {
+        nodes: vec![root],
+        ..Scene::default()
+    };
+
+    let mut mesh = Mesh::empty();
+    scene.for_each_world_transform(|node, world| {
+        if let Some(model) = &node.renderable {
+            mesh.append(model.mesh(), world);
+        }
+    });
+    Ok(Model::new(mesh, materials))
+}
+
+/// The node tree, and the materials that all of its models share
+fn read(path: &Path) -> Result<(Node, Vec<Material>), LoadError> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let Gltf { document, blob } = Gltf::from_slice(&fs::read(path)?).map_err(parse_error)?;
+
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            buffer::Source::Bin => blob
+                .clone()
+                .ok_or_else(|| parse_error("missing binary chunk"))?,
+            buffer::Source::Uri(uri) => read_uri(uri, directory)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(parse_error(format!(
+                "buffer {} is shorter than expected",
+                buffer.index()
+            )));
+        }
+        buffers.push(data);
+    }
+
+    let mut textures = Vec::new();
+    for image in document.images() {
+        let image = match image.source() {
+            gltf_image::Source::View { view, .. } => {
+                let start = view.offset();
+                let data = buffers
+                    .get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(start..start.checked_add(view.length())?))
+                    .ok_or_else(|| {
+                        parse_error(format!("buffer view {} is out of range", view.index()))
+                    })?;
+                image::load_from_memory(data)?
+            }
+            gltf_image::Source::Uri { uri, .. } => {
+                image::load_from_memory(&read_uri(uri, directory)?)?
+            }
+        };
+        textures.push(Rc::new(Texture::new(image)));
+    }
+
+    let mut materials: Vec<Material> = document
+        .materials()
+        .map(|material| load_material(&material, &textures))
+        .collect();
+    // For primitives without a material
+    let default_material = materials.len();
+    materials.push(Material::default());
+    // Primitives without normals fall back to the face normals
+    for material in materials.iter_mut() {
+        material.shading = Shading::Gouraud;
+    }
+
+    let models = document
+        .meshes()
+        .map(|mesh| {
+            let mesh = load_mesh(&mesh, &buffers, default_material)?;
+            Ok(Rc::new(Model::new(mesh, materials.clone())))
+        })
+        .collect::<Result<Vec<Rc<Model>>, LoadError>>()?;
+
+    let root = Node {
+        children: root_nodes(&document)
+            .iter()
+            .map(|node| load_node(node, &models))
+            .collect(),
+        ..Node::default()
+    };
+    Ok((root, materials))
+}
+
+fn parse_error<E: ToString>(error: E) -> LoadError {
+    LoadError::Parse {
+        line: 0,
+        message: error.to_string(),
+    }
+}
+
+/// Either an embedded base64 data URI or a path relative to the glTF file
+fn read_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, LoadError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        return match data.split_once(";base64,") {
+            Some((_, data)) => base64::decode(data).map_err(parse_error),
+            None => Err(parse_error("only base64 data URIs are supported")),
+        };
+    }
+
+    Ok(fs::read(directory.join(percent_decode(uri)?))?)
+}
+
+/// Relative URIs escape spaces and other characters as `%` and two hex digits, which can
+/// form UTF-8 sequences
+fn percent_decode(uri: &str) -> Result<String, LoadError> {
+    let invalid = || parse_error(format!("invalid escape in URI '{}'", uri));
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let value = after
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                .ok_or_else(invalid)?;
+            bytes.push(value);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn root_nodes(document: &Document) -> Vec<gltf::Node<'_>> {
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().collect(),
+        // Without scenes, every node that isn't a child of another node is a root
+        None => document
+            .nodes()
+            .filter(|node| {
+                !document
+                    .nodes()
+                    .any(|parent| parent.children().any(|child| child.index() == node.index()))
+            })
+            .collect(),
+    }
+}
+
+/// Only the base color of the PBR material is used
+fn load_material(material: &gltf::Material, textures: &[Rc<Texture>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    // The factor is linear, while textures and the terminal are sRGB
+    let [r, g, b, _] = pbr.base_color_factor().map(|v| (v as f64).powf(1. / 2.2));
+    let color = RgbColor::from_f64(r, g, b);
+
+    let cull_mode = if material.double_sided() {
+        CullMode::None
+    } else {
+        CullMode::Back
+    };
+
+    match pbr.base_color_texture() {
+        Some(info) => {
+            let texture = info.texture();
+            Material {
+                texture: Some(textures[texture.source().index()].clone()),
+                sampler: load_sampler(&texture.sampler()),
+                tint: color,
+                cull_mode,
+                ..Material::default()
+            }
+        }
+        None => Material {
+            cull_mode,
+            ..Material::from_color(color)
+        },
+    }
+}
+
+fn load_sampler(sampler: &gltf::texture::Sampler) -> Sampler {
+    let address_mode = |wrapping_mode| match wrapping_mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirroredRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    };
+    let filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMode::Nearest,
+        _ => FilterMode::Linear,
+    };
+    let mipmap_mode = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::Linear) => MipmapMode::None,
+        Some(MinFilter::NearestMipmapNearest) | Some(MinFilter::LinearMipmapNearest) => {
+            MipmapMode::Nearest
+        }
+        _ => MipmapMode::Linear,
+    };
+
+    Sampler {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        filter,
+        mipmap_mode,
+    }
+}
+
+/// From the Y-up coordinate system of glTF files, this is a half turn around X
+fn flip(x: f32, y: f32, z: f32) -> Vector3 {
+    Vector3::new(x as f64, -y as f64, -z as f64)
+}
+
+fn load_node(node: &gltf::Node, models: &[Rc<Model>]) -> Node {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    let [x, y, z, w] = rotation.map(|v| v as f64);
+    let [scale_x, scale_y, scale_z] = scale.map(|v| v as f64);
+
+    Node {
+        name: node.name().unwrap_or("").to_string(),
+        pos: flip(translation[0], translation[1], translation[2]),
+        // The same rotation, seen with the flipped axes
+        rotation: Quaternion::new(x, -y, -z, w),
+        size: Vector3::new(scale_x, scale_y, scale_z),
+        renderable: node.mesh().map(|mesh| models[mesh.index()].clone()),
+        children: node
+            .children()
+            .map(|child| load_node(&child, models))
+            .collect(),
+        ..Node::default()
+    }
+}
+
+/// Every triangle primitive becomes a group of the mesh
+fn load_mesh(
+    gltf_mesh: &gltf::Mesh,
+    buffers: &[Vec<u8>],
+    default_material: usize,
+) -> Result<Mesh, LoadError> {
+    let mut mesh = Mesh::empty();
+    for primitive in gltf_mesh.primitives() {
+        if primitive.mode() != mesh::Mode::Triangles {
+            continue;
+        }
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+
+        let positions: Vec<Vector3> = match reader.read_positions() {
+            Some(positions) => positions.map(|[x, y, z]| flip(x, y, z)).collect(),
+            None => continue,
+        };
+        let count = positions.len();
+        let normals: Vec<Vector3> = match reader.read_normals() {
+            Some(normals) => normals
+                .map(|[x, y, z]| flip(x, y, z).normalized())
+                .collect(),
+            None => vec![Vector3::zero(); count],
+        };
+        // The set that the base color texture uses.
+        // glTF texture coordinates start at the top left, like the textures.
+        let tex_coord = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map_or(0, |info| info.tex_coord());
+        let uvs: Vec<Vector2<f64>> = match reader.read_tex_coords(tex_coord) {
+            Some(uvs) => uvs
+                .into_f32()
+                .map(|[u, v]| Vector2::new(u as f64, v as f64))
+                .collect(),
+            None => vec![Vector2::new(0., 0.); count],
+        };
+        let colors: Vec<RgbColor> = match reader.read_colors(0) {
+            Some(colors) => colors
+                .into_rgb_f32()
+                .map(|[r, g, b]| {
+                    let gamma = |v: f32| (v as f64).powf(1. / 2.2);
+                    RgbColor::from_f64(gamma(r), gamma(g), gamma(b))
+                })
+                .collect(),
+            None => vec![RgbColor::WHITE; count],
+        };
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count as u32).collect(),
+        };
+        if normals.len() != count
+            || uvs.len() != count
+            || colors.len() != count
+            || indices.iter().any(|&index| index as usize >= count)
+        {
+            return Err(parse_error(format!(
+                "invalid primitive in mesh {}",
+                gltf_mesh.index()
+            )));
+        }
+
+        let offset = mesh.positions.len() as u32;
+        mesh.groups.push(MeshGroup {
+            start: mesh.indices.len(),
+            count: indices.len() / 3 * 3,
+            material: primitive.material().index().unwrap_or(default_material),
+        });
+        mesh.indices.extend(
+            indices[..indices.len() / 3 * 3]
+                .iter()
+                .map(|index| offset + index),
+        );
+        mesh.positions.extend(positions);
+        mesh.normals.extend(normals);
+        mesh.uvs.extend(uvs);
+        mesh.colors.extend(colors);
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// One textured triangle, the texture is red and green on top and blue and white at the bottom.
+    /// `image_view` is the byte range of an image in the buffer, instead of the texture file.
+    fn write_triangle(
+        directory: &Path,
+        indices: [u16; 3],
+        image_view: Option<(usize, usize)>,
+    ) -> PathBuf {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let image = RgbImage::from_fn(2, 2, |x, y| Rgb(colors[(y * 2 + x) as usize]));
+        DynamicImage::ImageRgb8(image)
+            .save(directory.join("tex \u{e9}.png"))
+            .unwrap();
+
+        let mut data = Vec::new();
+        for value in [
+            0., 0., 0., 1., 0., 0., 0., 1., 0., 0.25, 0.25, 0.75, 0.25, 0.25, 0.75,
+        ] {
+            data.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+        for index in indices.iter().chain(&[0]) {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let (image, views) = match image_view {
+            Some((offset, length)) => (
+                json!({ "bufferView": 3, "mimeType": "image/png" }),
+                json!([{ "buffer": 0, "byteOffset": offset, "byteLength": length }]),
+            ),
+            None => (json!({ "uri": "tex%20%C3%A9.png" }), json!([])),
+        };
+        let mut buffer_views = vec![
+            json!({ "buffer": 0, "byteOffset": 0, "byteLength": 36 }),
+            json!({ "buffer": 0, "byteOffset": 36, "byteLength": 24 }),
+            json!({ "buffer": 0, "byteOffset": 60, "byteLength": 6 }),
+        ];
+        buffer_views.extend(views.as_array().unwrap().iter().cloned());
+
+        let document = json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{ "primitives": [{
+                "attributes": { "POSITION": 0, "TEXCOORD_0": 1 },
+                "indices": 2,
+                "material": 0
+            }] }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+            "textures": [{ "source": 0, "sampler": 0 }],
+            "samplers": [{ "wrapS": 33071, "wrapT": 33648, "magFilter": 9728 }],
+            "images": [image],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0, 0, 0], "max": [1, 1, 0] },
+                { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" },
+                { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ],
+            "bufferViews": buffer_views,
+            "buffers": [{
+                "byteLength": data.len(),
+                "uri": format!("data:application/octet-stream;base64,{}", base64::encode(&data))
+            }]
+        });
+        let path = directory.join("model.gltf");
+        fs::write(&path, document.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn texture_coordinates_start_at_the_top_left() {
+        let directory = TestDirectory::new("gltf-uvs");
+        let model = load(&write_triangle(&directory, [0, 1, 2], None))
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        let material = &model.materials[0];
+        let texture = material.texture.as_ref().unwrap();
+        let sampler = Sampler {
+            mipmap_mode: MipmapMode::None,
+            ..material.sampler
+        };
+        let texels: Vec<(u8, u8, u8)> = model
+            .mesh()
+            .uvs
+            .iter()
+            .map(|uv| sampler.sample(texture, uv))
+            .map(|color| (color.r, color.g, color.b))
+            .collect();
+        assert_eq!(texels, [(255, 0, 0), (0, 255, 0), (0, 0, 255)]);
+    }
+
+    /// The triangle in a parent node with a child, and in another root node
+    fn write_hierarchy(directory: &Path) -> PathBuf {
+        let path = write_triangle(directory, [0, 1, 2], None);
+        let mut document: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let half = 0.5f64.sqrt();
+        document["scenes"] = json!([{ "nodes": [0, 2] }]);
+        document["nodes"] = json!([
+            {
+                "name": "parent",
+                "translation": [1, 2, 3],
+                "rotation": [0, half, 0, half],
+                "children": [1]
+            },
+            { "name": "child", "mesh": 0, "scale": [2, 2, 2] },
+            { "name": "other", "mesh": 0, "translation": [0, 0, -1] }
+        ]);
+        fs::write(&path, document.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn nodes_keep_their_hierarchy() {
+        let directory = TestDirectory::new("gltf-nodes");
+        let root =
+            load_scene(&write_hierarchy(&directory)).unwrap_or_else(|error| panic!("{}", error));
+
+        let names: Vec<&str> = root
+            .children
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(names, ["parent", "other"]);
+        let parent = &root.children[0];
+        assert!(parent.renderable.is_none());
+        assert_eq!((parent.pos.x, parent.pos.y, parent.pos.z), (1., -2., -3.));
+        let rotation = parent.rotation;
+        let half = 0.5f64.sqrt();
+        assert!((rotation.y + half).abs() < 1e-6 && (rotation.w - half).abs() < 1e-6);
+
+        let child = root.find("child").unwrap();
+        assert_eq!((child.size.x, child.size.y, child.size.z), (2., 2., 2.));
+        // Both nodes use the same mesh
+        let other = root.find("other").unwrap();
+        assert!(Rc::ptr_eq(
+            child.renderable.as_ref().unwrap(),
+            other.renderable.as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn flattened_model_has_the_world_positions() {
+        let directory = TestDirectory::new("gltf-flatten");
+        let model = load(&write_hierarchy(&directory)).unwrap_or_else(|error| panic!("{}", error));
+
+        assert_eq!(model.mesh().positions.len(), 6);
+        assert_eq!(model.mesh().indices, [0, 1, 2, 3, 4, 5]);
+        // `(1, 0, 0)` scaled by the child, turned around Y by the parent and moved, then flipped
+        let p = model.mesh().positions[1];
+        assert!((p - Vector3::new(1., -2., -1.)).length() < 1e-6);
+        // Only moved by the other node
+        let p = model.mesh().positions[4];
+        assert!((p - Vector3::new(1., 0., 1.)).length() < 1e-6);
+    }
+
+    #[test]
+    fn texture_uses_its_texture_coordinate_set() {
+        let directory = TestDirectory::new("gltf-tex-coord");
+        let path = write_triangle(&directory, [0, 1, 2], None);
+        let mut document: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        // The first set reads the positions, the texture uses the second one
+        document["accessors"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC2" }));
+        document["meshes"][0]["primitives"][0]["attributes"] =
+            json!({ "POSITION": 0, "TEXCOORD_0": 3, "TEXCOORD_1": 1 });
+        document["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"]["texCoord"] = json!(1);
+        fs::write(&path, document.to_string()).unwrap();
+
+        let model = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        let uvs: Vec<(f64, f64)> = model.mesh().uvs.iter().map(|uv| (uv.x, uv.y)).collect();
+        assert_eq!(uvs, [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75)]);
+    }
+
+    #[test]
+    fn samplers_keep_the_address_mode_of_each_axis() {
+        let directory = TestDirectory::new("gltf-sampler");
+        let model = load(&write_triangle(&directory, [0, 1, 2], None))
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        let sampler = model.materials[0].sampler;
+        assert!(sampler.address_mode_u == AddressMode::ClampToEdge);
+        assert!(sampler.address_mode_v == AddressMode::MirroredRepeat);
+        assert!(sampler.filter == FilterMode::Nearest);
+    }
+
+    #[test]
+    fn indices_out_of_range_are_errors() {
+        let directory = TestDirectory::new("gltf-indices");
+        assert!(load(&write_triangle(&directory, [0, 1, 3], None)).is_err());
+    }
+
+    #[test]
+    fn image_views_out_of_range_are_errors() {
+        let directory = TestDirectory::new("gltf-view");
+        assert!(load(&write_triangle(&directory, [0, 1, 2], Some((60, 100)))).is_err());
+        assert!(load(&write_triangle(
+            &directory,
+            [0, 1, 2],
+            Some((usize::MAX, 1))
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn uris_are_percent_decoded() {
+        assert_eq!(percent_decode("a%20b%C3%A9.png").unwrap(), "a b\u{e9}.png");
+        assert_eq!(percent_decode("plain.png").unwrap(), "plain.png");
+        for uri in ["a%2", "a%zz", "a%+1", "%FF"] {
+            assert!(percent_decode(uri).is_err(), "{}", uri);
+        }
+    }
+}
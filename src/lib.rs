@@ -0,0 +1,31 @@
+pub mod arcball;
+pub mod bounds;
+pub mod camera;
+pub mod clipping;
+pub mod cube;
+pub mod frustum;
+pub mod gltf_loader;
+pub mod light;
+pub mod material;
+pub mod matrix3;
+pub mod matrix4;
+pub mod mesh;
+pub mod model;
+pub mod obj;
+pub mod ply;
+pub mod quaternion;
+pub mod rasterizer;
+pub mod render_context;
+pub mod scene;
+pub mod scene_file;
+pub mod shadow_map;
+pub mod stl;
+pub mod terminal_renderer;
+#[cfg(test)]
+mod test_directory;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
+pub mod vector2;
+pub mod vector3;
+pub mod vector4;
@@ -0,0 +1,164 @@
+use crate::material::Material;
+use crate::terminal_renderer::RgbColor;
+use crate::vector3::Vector3;
+
+/// Positions and directions are in world space
+#[derive(Copy, Clone)]
+pub enum Light {
+    /// Reaches every surface equally, no matter which way it faces
+    Ambient { color: RgbColor, intensity: f64 },
+    /// Infinitely far away like the sun, `direction` is where the light travels to
+    Directional {
+        direction: Vector3,
+        color: RgbColor,
+        intensity: f64,
+        /// Whether objects block this light, see `ShadowMap`
+        shadows: bool,
+    },
+    /// Gets weaker with the distance and reaches nothing beyond `range`
+    Point {
+        position: Vector3,
+        color: RgbColor,
+        intensity: f64,
+        range: f64,
+    },
+}
+
+/// Some ambient light and a light from the top left, behind the default camera
+pub const DEFAULT_LIGHTS: [Light; 2] = [
+    Light::Ambient {
+        color: RgbColor::WHITE,
+        intensity: 0.25,
+    },
+    Light::Directional {
+        direction: Vector3 {
+            x: 1.,
+            y: 1.,
+            z: 2.,
+        },
+        color: RgbColor::WHITE,
+        intensity: 0.85,
+        shadows: true,
+    },
+];
+
+impl Light {
+    /// Unit direction from `position` towards the light, `None` for ambient light,
+    /// and how much light arrives there as linear RGB
+    fn incoming(&self, position: &Vector3) -> (Option<Vector3>, [f64; 3]) {
+        let (to_light, color, strength) = match self {
+            Light::Ambient { color, intensity } => (None, color, *intensity),
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+                ..
+            } => (Some((*direction * -1.).normalized()), color, *intensity),
+            Light::Point {
+                position: light_position,
+                color,
+                intensity,
+                range,
+            } => {
+                let to_light = *light_position - *position;
+                // Smooth falloff that reaches zero at the range
+                let attenuation = (1. - to_light.length() / range).clamp(0., 1.).powi(2);
+                (Some(to_light.normalized()), color, intensity * attenuation)
+            }
+        };
+
+        (
+            to_light,
+            [
+                color.r as f64 / 255. * strength,
+                color.g as f64 / 255. * strength,
+                color.b as f64 / 255. * strength,
+            ],
+        )
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        matches!(self, Light::Directional { shadows: true, .. })
+    }
+}
+
+/// Light reflected by a surface
+#[derive(Copy, Clone)]
+pub struct Lighting {
+    /// Multiplied with the surface color
+    pub diffuse: RgbColor,
+    /// Added on top, for highlights
+    pub specular: RgbColor,
+    /// What is left of `diffuse` where the lights that cast shadows are blocked
+    pub shadowed_diffuse: RgbColor,
+}
+
+impl Lighting {
+    /// Leaves the surface color as it is
+    pub const UNLIT: Lighting = Lighting {
+        diffuse: RgbColor::WHITE,
+        specular: RgbColor::BLACK,
+        shadowed_diffuse: RgbColor::WHITE,
+    };
+}
+
+/// Lambert diffuse and Blinn-Phong specular reflection of all `lights`,
+/// for a surface at `position` facing towards `normal` that is seen from `eye`
+pub fn blinn_phong(
+    lights: &[Light],
+    material: &Material,
+    position: &Vector3,
+    normal: &Vector3,
+    eye: &Vector3,
+) -> Lighting {
+    let to_eye = (*eye - *position).normalized();
+    let mut diffuse = [0.; 3];
+    let mut specular = [0.; 3];
+    let mut shadowed_diffuse = [0.; 3];
+
+    for light in lights {
+        let (to_light, incoming) = light.incoming(position);
+        let (lambert, highlight) = match to_light {
+            Some(to_light) => {
+                let lambert = normal.dot(&to_light).max(0.);
+                // No highlights on the side that faces away from the light
+                let highlight = if lambert > 0. {
+                    let halfway = (to_light + to_eye).normalized();
+                    normal.dot(&halfway).max(0.).powf(material.shininess)
+                } else {
+                    0.
+                };
+                (lambert, highlight)
+            }
+            None => (1., 0.),
+        };
+
+        let casts_shadows = light.casts_shadows();
+        for channel in 0..3 {
+            diffuse[channel] += incoming[channel] * lambert;
+            specular[channel] += incoming[channel] * highlight;
+            if !casts_shadows {
+                shadowed_diffuse[channel] += incoming[channel] * lambert;
+            }
+        }
+    }
+
+    let specular_color = [
+        material.specular.r,
+        material.specular.g,
+        material.specular.b,
+    ];
+    Lighting {
+        diffuse: RgbColor::from_f64(diffuse[0], diffuse[1], diffuse[2]),
+        specular: RgbColor::from_f64(
+            specular[0] * specular_color[0] as f64 / 255.,
+            specular[1] * specular_color[1] as f64 / 255.,
+            specular[2] * specular_color[2] as f64 / 255.,
+        ),
+        shadowed_diffuse: RgbColor::from_f64(
+            shadowed_diffuse[0],
+            shadowed_diffuse[1],
+            shadowed_diffuse[2],
+        ),
+    }
+}
@@ -1,135 +1,451 @@
 extern crate image;
 
-use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, RgbImage};
-use std::{io::stdout, time::Duration};
+mod cli;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
+use std::{
+    io::stdout,
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+    time::Duration,
+};
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, MouseButton},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers, MouseButton,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode},
     Result,
 };
-use cube::{Cube, Rectangle3D};
 use game_loop::game_loop;
-use vector3::Vector3;
-
-mod cube;
-mod terminal_renderer;
-mod vector2;
-mod vector3;
+use rust_cube::arcball;
+use rust_cube::camera::Camera;
+use rust_cube::cube::{Cube, CubeMaterials};
+use rust_cube::material::{Material, Shading};
+use rust_cube::model::Model;
+use rust_cube::quaternion::Quaternion;
+use rust_cube::render_context::{RenderContext, RenderMode};
+use rust_cube::scene::{Node, RenderStats, Scene};
+use rust_cube::scene_file::{self, FileWatcher};
+use rust_cube::terminal_renderer::{self, CellMode, ColorDepth, DrawBuffer};
+use rust_cube::texture::Texture;
+use rust_cube::vector2::Vector2;
+use rust_cube::vector3::Vector3;
 
 struct Game {
     pub draw_buffer: terminal_renderer::DrawBuffer,
-    pub rotation: Vector3,
+    pub camera: Camera,
+    pub rotation: Quaternion,
     pub mouse_down_pos: (u16, u16),
-    pub image: DynamicImage,
+    pub scene: Scene,
+    /// Reloaded whenever it changes
+    pub scene_file: Option<FileWatcher>,
+    /// Shown at the bottom of the terminal
+    pub status: Option<String>,
+    /// Path typed so far while asking for an image to load
+    pub prompt: Option<String>,
+    /// From `--texture` or the L key, also applied to the scene file after every reload
+    pub texture: Option<Rc<Texture>>,
+    pub render_mode: RenderMode,
+    pub color_depth: ColorDepth,
+    /// Overrides the shading of the materials
+    pub shading: Option<Shading>,
+    /// Degrees per second around the local axes
+    pub spin: Vector3,
+    /// Shows the statistics at the top of the terminal
+    pub debug: bool,
+    /// Of the last frame
+    pub stats: RenderStats,
 }
 
+/// The node that gets rotated with the mouse
+const MODEL_NODE: &str = "model";
+
 impl Game {
-    fn update(&mut self, seconds: f64, rotation: Vector3) {
+    fn update(&mut self, seconds: f64) {
+        self.reload_scene();
         self.draw_buffer.update_size();
+
+        let spin = Quaternion::from_euler(&(self.spin * seconds));
+        self.rotation = (self.rotation * spin).normalized();
+        self.scene.update(seconds);
+
+        self.draw();
+    }
+
+    fn draw(&mut self) {
         self.draw_buffer.clear();
+        self.camera.update_aspect_ratio(&self.draw_buffer);
 
-        let cube = Cube {
-            pos: Vector3::zero(),
-            size: Vector3::new(0.5, 0.5, 0.5),
-            euler_angles: rotation, //Vector3::new(10. * seconds, 0. * seconds, 0. * seconds),
+        if let Some(node) = self.scene.find_mut(MODEL_NODE) {
+            node.rotation = self.rotation;
+        }
+
+        let context = RenderContext {
+            camera: &self.camera,
+            mode: self.render_mode,
+            lights: &self.scene.lights,
+            shading: self.shading,
+            shadow_maps: &[],
+        };
+        self.stats = self.scene.render(&mut self.draw_buffer, &context);
+    }
+
+    /// Keeps showing the previous scene if the file has errors
+    fn reload_scene(&mut self) {
+        let watcher = match &mut self.scene_file {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        if !watcher.poll() {
+            return;
+        }
+
+        match scene_file::load(&watcher.path) {
+            Ok((scene, camera)) => {
+                self.scene = Scene {
+                    nodes: vec![Node {
+                        children: scene.nodes,
+                        ..Node::new(MODEL_NODE, None)
+                    }],
+                    lights: scene.lights,
+                };
+                if let (Some(texture), Some(node)) =
+                    (&self.texture, self.scene.find_mut(MODEL_NODE))
+                {
+                    replace_textures(node, texture);
+                }
+                if let Some(camera) = camera {
+                    self.camera = camera;
+                }
+                self.status = model_warning(&self.scene);
+            }
+            Err(error) => self.status = Some(format!("{}: {}", watcher.path.display(), error)),
+        }
+    }
+
+    /// Returns `false` to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(prompt) = &mut self.prompt {
+            match key.code {
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    prompt.push(c)
+                }
+                KeyCode::Backspace => {
+                    prompt.pop();
+                }
+                KeyCode::Enter => {
+                    let path = self.prompt.take().unwrap_or_default();
+                    self.set_texture(Path::new(path.trim()));
+                }
+                KeyCode::Esc => self.prompt = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => false,
+            KeyCode::Esc | KeyCode::Char('q') => false,
+            KeyCode::Char('l') => {
+                self.prompt = Some(String::new());
+                true
+            }
+            KeyCode::Char('s') => {
+                self.shading = match self.shading {
+                    None => Some(Shading::Flat),
+                    Some(Shading::Flat) => Some(Shading::Gouraud),
+                    Some(Shading::Gouraud) => Some(Shading::Phong),
+                    Some(Shading::Phong) | Some(Shading::Unlit) => None,
+                };
+                self.status = Some(
+                    match self.shading {
+                        None => "Shading of the materials",
+                        Some(Shading::Flat) => "Flat shading",
+                        Some(Shading::Gouraud) => "Gouraud shading",
+                        Some(Shading::Phong) => "Phong shading",
+                        Some(Shading::Unlit) => "Unlit",
+                    }
+                    .to_string(),
+                );
+                true
+            }
+            KeyCode::Char('b') => {
+                let (cell_mode, name) = match self.draw_buffer.cell_mode {
+                    CellMode::HalfBlock => (CellMode::Quadrant, "Quadrant blocks"),
+                    CellMode::Quadrant => (CellMode::Braille, "Braille"),
+                    CellMode::Braille => (CellMode::Underscore, "Underscores"),
+                    CellMode::Underscore => (CellMode::HalfBlock, "Half blocks"),
+                };
+                // The buffer gets resized in the next update
+                self.draw_buffer.cell_mode = cell_mode;
+                self.status = Some(name.to_string());
+                true
+            }
+            KeyCode::Char('d') => {
+                self.debug = !self.debug;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Replaces the textures of everything that the mouse rotates
+    fn set_texture(&mut self, path: &Path) {
+        let texture = match load_texture(path) {
+            Ok(texture) => texture,
+            Err(error) => {
+                self.status = Some(error);
+                return;
+            }
         };
 
-        // TODO: Move to draw function
-        cube.render(&mut self.draw_buffer, &self.image);
+        if let Some(node) = self.scene.find_mut(MODEL_NODE) {
+            replace_textures(node, &texture);
+        }
+        self.texture = Some(texture);
+        self.status = None;
+    }
 
-        // let rect = Rectangle3D {
-        //     top_left: Vector3::new(-0.6, 0.25, 0.5),
-        //     top_right: Vector3::new(-0.25, -0.6, 0.5),
-        //     bottom_right: Vector3::new(0.6, -0.25, 0.5),
-        //     bottom_left: Vector3::new(0.25, 0.6, 0.5),
-        // };
+    /// Terminal cell to the `-1` to `1` viewport range
+    fn to_viewport(&self, (column, row): (u16, u16)) -> Vector2<f64> {
+        // In pixel rows, so that both axes use the same unit
+        let (cell_width, cell_height) = self.draw_buffer.cell_mode.pixels();
+        let pixel_aspect_ratio = self.draw_buffer.cell_mode.pixel_aspect_ratio();
+        let width = self.draw_buffer.width as f64 * pixel_aspect_ratio;
+        let height = self.draw_buffer.height as f64;
+        let half_size = 0.5 * width.min(height);
+        Vector2::new(
+            ((column * cell_width) as f64 * pixel_aspect_ratio - 0.5 * width) / half_size,
+            ((row * cell_height) as f64 - 0.5 * height) / half_size,
+        )
+    }
 
-        // let rect = Rectangle3D {
-        //     top_left: Vector3::new(-0.6, 0.25, -0.5),
-        //     top_right: Vector3::new(-0.25, -0.6, -0.5),
-        //     bottom_right: Vector3::new(-0.25, -0.6, 0.5),
-        //     bottom_left: Vector3::new(-0.6, 0.25, 0.5),
-        // };
+    /// Arcball rotation, so that the point under the cursor follows it
+    fn drag(&mut self, mouse_pos: (u16, u16)) {
+        let from = self.to_viewport(self.mouse_down_pos);
+        let to = self.to_viewport(mouse_pos);
+        self.mouse_down_pos = mouse_pos;
 
-        // rect.render(&mut self.draw_buffer, 0);
+        let camera_rotation = self.camera.transform().rotation;
+        let view_rotation = arcball::drag_rotation(&from, &to);
+        let world_rotation = camera_rotation * view_rotation * camera_rotation.conjugate();
+        self.rotation = (world_rotation * self.rotation).normalized();
     }
 
     // TODO: Make immutable self
     fn render(&mut self) {
         // TODO: Maybe don't ignore all errors?
-        match terminal_renderer::render(&self.draw_buffer) {
-            Ok(_) => {}
-            Err(_) => {}
+        let _ = terminal_renderer::render(&self.draw_buffer, self.color_depth);
+        if self.debug {
+            let _ = terminal_renderer::render_debug(&format!(
+                "Objects: {}  Culled: {}",
+                self.stats.objects, self.stats.culled
+            ));
         }
+        if let Some(prompt) = &self.prompt {
+            let _ = terminal_renderer::render_status(&format!("Load image: {}_", prompt));
+        } else if let Some(status) = &self.status {
+            let _ = terminal_renderer::render_status(status);
+        }
+    }
+
+    /// PNG image or ANSI text, depending on the extension
+    fn save(&self, path: &Path) -> std::result::Result<(), String> {
+        let is_image = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if is_image {
+            return self
+                .draw_buffer
+                .to_image()
+                .save(path)
+                .map_err(|error| error.to_string());
+        }
+
+        let mut file = BufWriter::new(File::create(path).map_err(|error| error.to_string())?);
+        terminal_renderer::write_ansi(&mut file, &self.draw_buffer, self.color_depth)
+            .map_err(|error| error.to_string())
     }
 }
 
+fn exit_with_error(message: impl Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// First problem of the models that still loaded, for the status line
+fn model_warning(scene: &Scene) -> Option<String> {
+    let mut warning = None;
+    scene.for_each_world_transform(|node, _| {
+        if let Some(model) = &node.renderable {
+            warning = warning.take().or_else(|| model.warnings.first().cloned());
+        }
+    });
+    warning
+}
+
+/// The error is meant for the status line
+fn load_texture(path: &Path) -> std::result::Result<Rc<Texture>, String> {
+    image::open(path)
+        .map(|image| Rc::new(Texture::new(image)))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+fn replace_textures(node: &mut Node, texture: &Rc<Texture>) {
+    node.for_each_model_mut(&mut |model| {
+        for material in model.materials.iter_mut() {
+            material.texture = Some(texture.clone());
+        }
+    });
+}
+
 fn main() -> Result<()> {
-    let img = image::open("cat.png").unwrap();
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}\n\nFor more information try --help", error);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    // A texture that can't be loaded is reported in the status line instead of stopping the program
+    let mut status = None;
+    let texture_path = match (&options.texture, &options.file) {
+        (Some(path), _) => Some(path.clone()),
+        (None, None) => Some(PathBuf::from("cat.png")),
+        (None, Some(_)) => None,
+    };
+    let texture = match texture_path.as_deref().map(load_texture) {
+        Some(Ok(texture)) => Some(texture),
+        Some(Err(error)) => {
+            status = Some(error);
+            None
+        }
+        None => None,
+    };
+    let (nodes, scene_file) = match &options.file {
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json") =>
+        {
+            (Vec::new(), Some(FileWatcher::new(path)))
+        }
+        Some(path) => match Node::load(path) {
+            Ok(mut node) => {
+                if let Some(texture) = &texture {
+                    replace_textures(&mut node, texture);
+                }
+                (vec![node], None)
+            }
+            Err(error) => exit_with_error(format!("failed to load {}: {}", path.display(), error)),
+        },
+        None => {
+            let texture = texture
+                .clone()
+                .unwrap_or_else(|| Rc::new(Texture::checkerboard(64, 8)));
+            let cube = Cube {
+                pos: Vector3::zero(),
+                size: Vector3::new(1., 1., 1.),
+                euler_angles: Vector3::zero(),
+                materials: CubeMaterials::uniform(Material::from_texture(texture)),
+            };
+            (vec![Node::new("", Some(Rc::new(Model::from(&cube))))], None)
+        }
+    };
+    // Scene files are loaded in the first update
+    let scene = Scene {
+        nodes: vec![Node {
+            size: Vector3::new(0.5, 0.5, 0.5),
+            children: nodes,
+            ..Node::new(MODEL_NODE, None)
+        }],
+        ..Scene::default()
+    };
+    let status = status.or_else(|| model_warning(&scene));
 
-    let game = Game {
-        draw_buffer: terminal_renderer::DrawBuffer::new(),
-        rotation: Vector3::zero(),
+    let draw_buffer = match options.output {
+        // The options already rejected sizes that are too large
+        Some(_) => DrawBuffer::with_size(options.size.0, options.size.1, options.cell_mode)
+            .unwrap_or_else(|| exit_with_error("the output size is too large")),
+        None => DrawBuffer::new(options.cell_mode),
+    };
+    let mut game = Game {
+        draw_buffer,
+        camera: Camera::default(),
+        rotation: Quaternion::from_euler(&options.rotation),
         mouse_down_pos: (0, 0),
-        image: img,
+        scene,
+        scene_file,
+        status,
+        prompt: None,
+        texture,
+        render_mode: options.mode,
+        color_depth: options.color_depth,
+        shading: options.shading,
+        spin: options.spin,
+        debug: false,
+        stats: RenderStats::default(),
     };
 
-    //enable_raw_mode()?;
+    if let Some(output) = &options.output {
+        if let Some(status) = game.status.take() {
+            eprintln!("warning: {}", status);
+        }
+        game.reload_scene();
+        if let Some(status) = &game.status {
+            exit_with_error(status);
+        }
+        game.draw();
+        if let Err(error) = game.save(output) {
+            exit_with_error(format!("failed to save {}: {}", output.display(), error));
+        }
+        return Ok(());
+    }
+
+    if let Some(status) = &mut game.status {
+        status.push_str(" - press L to load another image");
+    }
+
+    // Raw mode so that keys arrive right away, for typing paths
+    enable_raw_mode()?;
     execute!(stdout(), EnableMouseCapture)?;
 
     game_loop(
         game,
-        10,
+        options.fps,
         0.5,
         |g| {
-            let has_event = event::poll(Duration::from_secs(0)).unwrap_or(false);
-            if has_event {
-                match event::read() {
-                    Ok(ev) => match ev {
-                        event::Event::Key(k) => {
-                            if k.code == event::KeyCode::Esc {
-                                g.exit();
-                            }
-                        }
+            // All pending events, typing a path sends one per key
+            while event::poll(Duration::from_secs(0)).unwrap_or(false) {
+                if let Ok(ev) = event::read() {
+                    match ev {
+                        event::Event::Key(key) if !g.game.handle_key(key) => g.exit(),
                         event::Event::Mouse(m) => match m.kind {
-                            event::MouseEventKind::Down(button) => {
-                                if button == MouseButton::Left {
-                                    g.game.mouse_down_pos = (m.column, m.row);
-                                }
-                            }
-                            event::MouseEventKind::Drag(button) => {
-                                if button == MouseButton::Left {
-                                    let delta = (
-                                        m.column as i16 - g.game.mouse_down_pos.0 as i16,
-                                        m.row as i16 - g.game.mouse_down_pos.1 as i16,
-                                    );
-                                    g.game.mouse_down_pos = (m.column, m.row);
-                                    g.game.rotation.y += delta.0 as f64;
-                                    g.game.rotation.x -= delta.1 as f64;
-                                }
+                            event::MouseEventKind::Down(MouseButton::Left) => {
+                                g.game.mouse_down_pos = (m.column, m.row);
                             }
-                            event::MouseEventKind::Up(button) => {
-                                if button == MouseButton::Left {
-                                    let delta = (
-                                        m.column as i16 - g.game.mouse_down_pos.0 as i16,
-                                        m.row as i16 - g.game.mouse_down_pos.1 as i16,
-                                    );
-                                    g.game.mouse_down_pos = (m.column, m.row);
-                                    g.game.rotation.y += delta.0 as f64;
-                                    g.game.rotation.x -= delta.1 as f64;
-                                }
+                            event::MouseEventKind::Drag(MouseButton::Left)
+                            | event::MouseEventKind::Up(MouseButton::Left) => {
+                                g.game.drag((m.column, m.row));
                             }
                             _ => {}
                         },
                         _ => {}
-                    },
-                    Err(_) => {}
+                    }
                 }
             }
 
-            g.game.update(g.running_time(), g.game.rotation);
+            g.game.update(g.fixed_time_step());
         },
         |g| {
             g.game.render();
@@ -137,7 +453,7 @@ fn main() -> Result<()> {
     );
 
     execute!(stdout(), DisableMouseCapture)?;
-    // disable_raw_mode()?;
+    disable_raw_mode()?;
 
     Ok(())
 }
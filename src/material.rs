@@ -0,0 +1,91 @@
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::terminal_renderer::RgbColor;
+use crate::texture::{Sampler, Texture};
+use crate::vector2::Vector2;
+
+/// How the surface reacts to its orientation
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shading {
+    /// Only the material color
+    Unlit,
+    /// Lit once per triangle, with the face normal
+    Flat,
+    /// Lit at the vertices with the vertex normals and interpolated in between,
+    /// vertices without a normal use the face normal
+    Gouraud,
+    /// Lit at every pixel with the interpolated vertex normals
+    Phong,
+}
+
+/// Which sides of the triangles are skipped, the front is the side that the normal points to
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CullMode {
+    /// For closed models, their insides can't be seen anyway
+    Back,
+    /// For models that are seen from the inside, like rooms
+    Front,
+    /// Both sides are drawn, for planes and other open surfaces
+    None,
+}
+
+#[derive(Clone)]
+pub struct Material {
+    /// Used where there is no texture
+    pub base_color: RgbColor,
+    pub texture: Option<Rc<Texture>>,
+    pub sampler: Sampler,
+    /// Multiplied with the base color or the texture
+    pub tint: RgbColor,
+    pub shading: Shading,
+    /// Color of the Blinn-Phong highlights, black for none
+    pub specular: RgbColor,
+    /// Higher values make smaller and sharper highlights
+    pub shininess: f64,
+    pub cull_mode: CullMode,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            base_color: RgbColor::WHITE,
+            texture: None,
+            sampler: Sampler::default(),
+            tint: RgbColor::WHITE,
+            shading: Shading::Flat,
+            specular: RgbColor::new(77, 77, 77),
+            shininess: 32.,
+            cull_mode: CullMode::Back,
+        }
+    }
+}
+
+impl Material {
+    pub fn from_color(base_color: RgbColor) -> Material {
+        Material {
+            base_color,
+            ..Material::default()
+        }
+    }
+
+    pub fn from_texture(texture: Rc<Texture>) -> Material {
+        Material {
+            texture: Some(texture),
+            ..Material::default()
+        }
+    }
+
+    /// Color at the texture coordinate `uv`, `uv_dx` and `uv_dy` are the differences to the neighbouring pixels
+    pub fn shade(&self, uv: &Vector2<f64>, uv_dx: &Vector2<f64>, uv_dy: &Vector2<f64>) -> RgbColor {
+        let color = match &self.texture {
+            Some(texture) => self.sampler.sample_grad(texture, uv, uv_dx, uv_dy),
+            None => self.base_color,
+        };
+
+        color.multiply(&self.tint)
+    }
+}
@@ -0,0 +1,150 @@
+use crate::vector3::Vector3;
+
+/// Row-major 3x3 matrix, vectors are multiplied from the right
+#[derive(Copy, Clone)]
+pub struct Matrix3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Matrix3 {
+    pub fn new(m: [[f64; 3]; 3]) -> Matrix3 {
+        Matrix3 { m }
+    }
+
+    pub fn identity() -> Matrix3 {
+        Matrix3 {
+            m: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+        }
+    }
+
+    pub fn scale(scale: &Vector3) -> Matrix3 {
+        Matrix3 {
+            m: [[scale.x, 0., 0.], [0., scale.y, 0.], [0., 0., scale.z]],
+        }
+    }
+
+    /// Same convention as `Vector3::rotate_euler`
+    pub fn rotation_euler(euler_angles: &Vector3) -> Matrix3 {
+        let (sin_x, cos_x) = euler_angles.x.to_radians().sin_cos();
+        let (sin_y, cos_y) = euler_angles.y.to_radians().sin_cos();
+        let (sin_z, cos_z) = euler_angles.z.to_radians().sin_cos();
+
+        let rotation_x = Matrix3::new([[1., 0., 0.], [0., cos_x, -sin_x], [0., sin_x, cos_x]]);
+        let rotation_y = Matrix3::new([[cos_y, 0., sin_y], [0., 1., 0.], [-sin_y, 0., cos_y]]);
+        let rotation_z = Matrix3::new([[cos_z, -sin_z, 0.], [sin_z, cos_z, 0.], [0., 0., 1.]]);
+
+        rotation_z * rotation_y * rotation_x
+    }
+
+    pub fn transpose(&self) -> Matrix3 {
+        let mut result = Matrix3::identity();
+        for row in 0..3 {
+            for column in 0..3 {
+                result.m[row][column] = self.m[column][row];
+            }
+        }
+        result
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix3> {
+        let determinant = self.determinant();
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let m = &self.m;
+        let inverse_determinant = 1. / determinant;
+        let mut result = Matrix3::identity();
+        for row in 0..3 {
+            for column in 0..3 {
+                // Cofactor of the transposed position, rows and columns wrap around so the sign is built in
+                let (r0, r1) = ((column + 1) % 3, (column + 2) % 3);
+                let (c0, c1) = ((row + 1) % 3, (row + 2) % 3);
+                result.m[row][column] =
+                    (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) * inverse_determinant;
+            }
+        }
+        Some(result)
+    }
+
+    pub fn transform(&self, v: &Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+impl std::ops::Mul for Matrix3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut result = Matrix3::new([[0.; 3]; 3]);
+        for row in 0..3 {
+            for column in 0..3 {
+                for i in 0..3 {
+                    result.m[row][column] += self.m[row][i] * rhs.m[i][column];
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(matrix: &Matrix3) {
+        for row in 0..3 {
+            for column in 0..3 {
+                let expected = if row == column { 1. } else { 0. };
+                assert!(
+                    (matrix.m[row][column] - expected).abs() < 1e-9,
+                    "{:?}",
+                    matrix.m
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let matrix = Matrix3::rotation_euler(&Vector3::new(30., -45., 60.))
+            * Matrix3::scale(&Vector3::new(2., 0.5, 3.));
+        let inverse = matrix.inverse().unwrap();
+        assert_identity(&(matrix * inverse));
+        assert_identity(&(inverse * matrix));
+    }
+
+    #[test]
+    fn inverse_of_rotation_is_transpose() {
+        let rotation = Matrix3::rotation_euler(&Vector3::new(10., 20., 30.));
+        let inverse = rotation.inverse().unwrap();
+        let transpose = rotation.transpose();
+        for row in 0..3 {
+            for column in 0..3 {
+                assert!((inverse.m[row][column] - transpose.m[row][column]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix3::scale(&Vector3::new(1., 0., 1.))
+            .inverse()
+            .is_none());
+        let rows = Matrix3::new([[1., 2., 3.], [2., 4., 6.], [0., 1., 0.]]);
+        assert!(rows.inverse().is_none());
+    }
+}
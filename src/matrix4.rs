@@ -0,0 +1,241 @@
+use crate::matrix3::Matrix3;
+use crate::vector3::Vector3;
+use crate::vector4::Vector4;
+
+/// Row-major 4x4 matrix, vectors are multiplied from the right
+#[derive(Copy, Clone)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4 {
+            m: [
+                [1., 0., 0., 0.],
+                [0., 1., 0., 0.],
+                [0., 0., 1., 0.],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    pub fn translation(translation: &Vector3) -> Matrix4 {
+        let mut result = Matrix4::identity();
+        result.m[0][3] = translation.x;
+        result.m[1][3] = translation.y;
+        result.m[2][3] = translation.z;
+        result
+    }
+
+    pub fn scale(scale: &Vector3) -> Matrix4 {
+        Matrix3::scale(scale).into()
+    }
+
+    /// Same convention as `Vector3::rotate_euler`
+    pub fn rotation_euler(euler_angles: &Vector3) -> Matrix4 {
+        Matrix3::rotation_euler(euler_angles).into()
+    }
+
+    /// View matrix for an eye looking at `target`.
+    ///
+    /// The camera looks down +Z with +Y pointing downwards, so `up` ends up as -Y on screen.
+    pub fn look_at(eye: &Vector3, target: &Vector3, up: &Vector3) -> Matrix4 {
+        let forward = (*target - *eye).normalized();
+        let right = forward.cross(up).normalized();
+        let down = forward.cross(&right);
+
+        Matrix4::new([
+            [right.x, right.y, right.z, -right.dot(eye)],
+            [down.x, down.y, down.z, -down.dot(eye)],
+            [forward.x, forward.y, forward.z, -forward.dot(eye)],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Maps the view frustum to `-1` to `1` on X and Y, and `0` (near) to `1` (far) on Z.
+    ///
+    /// `fov` is the vertical field of view in degrees.
+    pub fn perspective(fov: f64, aspect_ratio: f64, near: f64, far: f64) -> Matrix4 {
+        let tan_half_fov = (fov.to_radians() * 0.5).tan();
+
+        Matrix4::new([
+            [1. / (tan_half_fov * aspect_ratio), 0., 0., 0.],
+            [0., 1. / tan_half_fov, 0., 0.],
+            [0., 0., far / (far - near), -far * near / (far - near)],
+            [0., 0., 1., 0.],
+        ])
+    }
+
+    /// Maps a box to `-1` to `1` on X and Y, and `0` (near) to `1` (far) on Z, without any perspective
+    pub fn orthographic(half_width: f64, half_height: f64, near: f64, far: f64) -> Matrix4 {
+        Matrix4::new([
+            [1. / half_width, 0., 0., 0.],
+            [0., 1. / half_height, 0., 0.],
+            [0., 0., 1. / (far - near), -near / (far - near)],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut result = Matrix4::identity();
+        for row in 0..4 {
+            for column in 0..4 {
+                result.m[row][column] = self.m[column][row];
+            }
+        }
+        result
+    }
+
+    /// Returns `None` if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut m = self.m;
+        let mut result = Matrix4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|a, b| m[*a][column].abs().total_cmp(&m[*b][column].abs()))
+                .unwrap_or(column);
+            if m[pivot][column].abs() < f64::EPSILON {
+                return None;
+            }
+            m.swap(column, pivot);
+            result.swap(column, pivot);
+
+            let inverse_pivot = 1. / m[column][column];
+            for i in 0..4 {
+                m[column][i] *= inverse_pivot;
+                result[column][i] *= inverse_pivot;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = m[row][column];
+                for i in 0..4 {
+                    m[row][i] -= factor * m[column][i];
+                    result[row][i] -= factor * result[column][i];
+                }
+            }
+        }
+
+        Some(Matrix4::new(result))
+    }
+
+    /// The upper left 3x3 part, without the translation
+    pub fn to_matrix3(&self) -> Matrix3 {
+        let m = &self.m;
+        Matrix3::new([
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ])
+    }
+
+    pub fn transform(&self, v: &Vector4) -> Vector4 {
+        let m = &self.m;
+        Vector4 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * v.w,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * v.w,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * v.w,
+            w: m[3][0] * v.x + m[3][1] * v.y + m[3][2] * v.z + m[3][3] * v.w,
+        }
+    }
+
+    /// Transforms a point, including the perspective divide
+    pub fn transform_point(&self, v: &Vector3) -> Vector3 {
+        self.transform(&Vector4::from_point(v)).perspective_divide()
+    }
+
+    /// Transforms a direction, ignoring the translation
+    pub fn transform_vector(&self, v: &Vector3) -> Vector3 {
+        self.to_matrix3().transform(v)
+    }
+}
+
+impl From<Matrix3> for Matrix4 {
+    fn from(item: Matrix3) -> Self {
+        let m = &item.m;
+        Matrix4::new([
+            [m[0][0], m[0][1], m[0][2], 0.],
+            [m[1][0], m[1][1], m[1][2], 0.],
+            [m[2][0], m[2][1], m[2][2], 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+impl std::ops::Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut result = Matrix4::new([[0.; 4]; 4]);
+        for row in 0..4 {
+            for column in 0..4 {
+                for i in 0..4 {
+                    result.m[row][column] += self.m[row][i] * rhs.m[i][column];
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(matrix: &Matrix4) {
+        for row in 0..4 {
+            for column in 0..4 {
+                let expected = if row == column { 1. } else { 0. };
+                assert!(
+                    (matrix.m[row][column] - expected).abs() < 1e-9,
+                    "{:?}",
+                    matrix.m
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let matrix = Matrix4::translation(&Vector3::new(1., -2., 3.))
+            * Matrix4::rotation_euler(&Vector3::new(30., -45., 60.))
+            * Matrix4::scale(&Vector3::new(2., 0.5, 3.));
+        let inverse = matrix.inverse().unwrap();
+        assert_identity(&(matrix * inverse));
+        assert_identity(&(inverse * matrix));
+    }
+
+    #[test]
+    fn inverse_needs_pivoting() {
+        // Zero on the diagonal, fails without swapping rows
+        let matrix = Matrix4::new([
+            [0., 1., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 0., 0., 2.],
+            [0., 0., 3., 0.],
+        ]);
+        assert_identity(&(matrix * matrix.inverse().unwrap()));
+
+        let projection = Matrix4::perspective(60., 1.5, 0.1, 100.);
+        assert_identity(&(projection * projection.inverse().unwrap()));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scale(&Vector3::new(1., 1., 0.))
+            .inverse()
+            .is_none());
+        let mut matrix = Matrix4::identity();
+        matrix.m[3] = matrix.m[0];
+        assert!(matrix.inverse().is_none());
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::bounds::Aabb;
+use crate::material::{CullMode, Material, Shading};
+use crate::matrix3::Matrix3;
+use crate::matrix4::Matrix4;
+use crate::render_context::RenderContext;
+use crate::shadow_map::ShadowMap;
+use crate::terminal_renderer::{DrawBuffer, RgbColor};
+use crate::triangle::{Triangle3D, Vertex};
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// A range of triangles that are drawn with the same material
+#[derive(Copy, Clone)]
+pub struct MeshGroup {
+    /// Offset into `Mesh::indices`
+    pub start: usize,
+    /// Number of indices, three per triangle
+    pub count: usize,
+    /// Index into the materials that the mesh is rendered with
+    pub material: usize,
+}
+
+/// Indexed triangle mesh.
+///
+/// Triangles are counterclockwise on screen when looking at their front side.
+/// `normals`, `uvs` and `colors` are either empty or have one entry per position.
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub uvs: Vec<Vector2<f64>>,
+    pub colors: Vec<RgbColor>,
+    /// Three indices per triangle
+    pub indices: Vec<u32>,
+    pub groups: Vec<MeshGroup>,
+}
+
+impl Mesh {
+    /// A mesh with a single group that uses the first material
+    pub fn new(
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        uvs: Vec<Vector2<f64>>,
+        indices: Vec<u32>,
+    ) -> Mesh {
+        let groups = vec![MeshGroup {
+            start: 0,
+            count: indices.len(),
+            material: 0,
+        }];
+
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            colors: Vec::new(),
+            indices,
+            groups,
+        }
+    }
+
+    /// Without vertices or groups, for loaders that add their own groups
+    pub fn empty() -> Mesh {
+        Mesh {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Unit cube from `-1` to `1`, with one group per face in the same order as `Cube::get_faces`
+    pub fn cube() -> Mesh {
+        crate::cube::Cube::unit().to_mesh()
+    }
+
+    /// Square from `-1` to `1` on the X-Z plane, facing upwards (-Y)
+    pub fn plane() -> Mesh {
+        let positions = vec![
+            Vector3::new(-1., 0., 1.),
+            Vector3::new(1., 0., 1.),
+            Vector3::new(1., 0., -1.),
+            Vector3::new(-1., 0., -1.),
+        ];
+        let normals = vec![Vector3::new(0., -1., 0.); 4];
+        let uvs = vec![
+            Vector2::new(0., 0.),
+            Vector2::new(1., 0.),
+            Vector2::new(1., 1.),
+            Vector2::new(0., 1.),
+        ];
+
+        Mesh::new(positions, normals, uvs, vec![0, 2, 1, 0, 3, 2])
+    }
+
+    /// Sphere with a radius of `1`, made out of `rings` horizontal bands that are each split into `segments`
+    pub fn uv_sphere(segments: u32, rings: u32) -> Mesh {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        for ring in 0..=rings {
+            // Starting at the top, which is -Y
+            let theta = PI * ring as f64 / rings as f64;
+            for segment in 0..=segments {
+                let phi = 2. * PI * segment as f64 / segments as f64;
+                positions.push(Vector3::new(
+                    theta.sin() * phi.cos(),
+                    -theta.cos(),
+                    theta.sin() * phi.sin(),
+                ));
+                uvs.push(Vector2::new(
+                    segment as f64 / segments as f64,
+                    ring as f64 / rings as f64,
+                ));
+            }
+        }
+
+        let indices = grid_indices(segments, rings);
+        Mesh::new(positions.clone(), positions, uvs, indices)
+    }
+
+    /// Sphere with a radius of `1`, made by repeatedly splitting the triangles of an icosahedron
+    pub fn icosphere(subdivisions: u32) -> Mesh {
+        let t = (1. + 5_f64.sqrt()) / 2.;
+        let mut positions: Vec<Vector3> = [
+            (-1., t, 0.),
+            (1., t, 0.),
+            (-1., -t, 0.),
+            (1., -t, 0.),
+            (0., -1., t),
+            (0., 1., t),
+            (0., -1., -t),
+            (0., 1., -t),
+            (t, 0., -1.),
+            (t, 0., 1.),
+            (-t, 0., -1.),
+            (-t, 0., 1.),
+        ]
+        .iter()
+        .map(|&(x, y, z)| Vector3::new(x, y, z).normalized())
+        .collect();
+
+        let mut indices: Vec<u32> = vec![
+            0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11, 1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7,
+            6, 7, 1, 8, 3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9, 4, 9, 5, 2, 4, 11, 6, 2, 10,
+            8, 6, 7, 9, 8, 1,
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vector3>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let position = (positions[a as usize] + positions[b as usize]).normalized();
+                    positions.push(position);
+                    (positions.len() - 1) as u32
+                })
+            };
+
+            let mut subdivided = Vec::with_capacity(indices.len() * 4);
+            for triangle in indices.chunks_exact(3) {
+                let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                subdivided.extend_from_slice(&[a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca]);
+            }
+            indices = subdivided;
+        }
+
+        // Spherical projection, the same as the UV sphere
+        let uvs = positions
+            .iter()
+            .map(|p| {
+                Vector2::new(
+                    p.z.atan2(p.x).rem_euclid(2. * PI) / (2. * PI),
+                    (-p.y).acos() / PI,
+                )
+            })
+            .collect();
+
+        Mesh::new(positions.clone(), positions, uvs, indices)
+    }
+
+    /// Cylinder with a radius of `1` from `-1` to `1` on the Y axis, including the caps
+    pub fn cylinder(segments: u32) -> Mesh {
+        let segments = segments.max(3);
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        for row in 0..=1 {
+            let y = if row == 0 { -1. } else { 1. };
+            for segment in 0..=segments {
+                let phi = 2. * PI * segment as f64 / segments as f64;
+                positions.push(Vector3::new(phi.cos(), y, phi.sin()));
+                normals.push(Vector3::new(phi.cos(), 0., phi.sin()));
+                uvs.push(Vector2::new(segment as f64 / segments as f64, row as f64));
+            }
+        }
+        let mut mesh = Mesh::new(positions, normals, uvs, grid_indices(segments, 1));
+
+        mesh.add_cap(segments, -1., 1.);
+        mesh.add_cap(segments, 1., 1.);
+        mesh
+    }
+
+    /// Cone with the tip at `-1` and a base with a radius of `1` at `1` on the Y axis
+    pub fn cone(segments: u32) -> Mesh {
+        let segments = segments.max(3);
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        for row in 0..=1 {
+            // The tip is one vertex per segment, so that each one can have its own normal
+            let (y, radius) = if row == 0 { (-1., 0.) } else { (1., 1.) };
+            for segment in 0..=segments {
+                let phi = 2. * PI * segment as f64 / segments as f64;
+                positions.push(Vector3::new(radius * phi.cos(), y, radius * phi.sin()));
+                normals.push(Vector3::new(phi.cos(), -0.5, phi.sin()).normalized());
+                uvs.push(Vector2::new(segment as f64 / segments as f64, row as f64));
+            }
+        }
+        let mut mesh = Mesh::new(positions, normals, uvs, grid_indices(segments, 1));
+
+        mesh.add_cap(segments, 1., 1.);
+        mesh
+    }
+
+    /// Ring on the X-Z plane, `major_radius` is the distance to the center of the tube
+    pub fn torus(major_radius: f64, minor_radius: f64, segments: u32, sides: u32) -> Mesh {
+        let segments = segments.max(3);
+        let sides = sides.max(3);
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        for side in 0..=sides {
+            let v = 2. * PI * side as f64 / sides as f64;
+            for segment in 0..=segments {
+                let u = 2. * PI * segment as f64 / segments as f64;
+                let normal = Vector3::new(v.cos() * u.cos(), v.sin(), v.cos() * u.sin());
+                let center = Vector3::new(major_radius * u.cos(), 0., major_radius * u.sin());
+                positions.push(center + normal * minor_radius);
+                normals.push(normal);
+                uvs.push(Vector2::new(
+                    segment as f64 / segments as f64,
+                    side as f64 / sides as f64,
+                ));
+            }
+        }
+
+        Mesh::new(positions, normals, uvs, grid_indices(segments, sides))
+    }
+
+    /// Disk at the height `y`, facing upwards (-Y) if `y` is negative and downwards otherwise
+    fn add_cap(&mut self, segments: u32, y: f64, radius: f64) {
+        let normal = Vector3::new(0., y.signum(), 0.);
+        let center = self.positions.len() as u32;
+        self.positions.push(Vector3::new(0., y, 0.));
+        self.normals.push(normal);
+        self.uvs.push(Vector2::new(0.5, 0.5));
+
+        for segment in 0..=segments {
+            let phi = 2. * PI * segment as f64 / segments as f64;
+            self.positions
+                .push(Vector3::new(radius * phi.cos(), y, radius * phi.sin()));
+            self.normals.push(normal);
+            self.uvs
+                .push(Vector2::new(0.5 + 0.5 * phi.cos(), 0.5 + 0.5 * phi.sin()));
+        }
+
+        for segment in 0..segments {
+            let current = center + 1 + segment;
+            if y < 0. {
+                self.indices
+                    .extend_from_slice(&[center, current, current + 1]);
+            } else {
+                self.indices
+                    .extend_from_slice(&[center, current + 1, current]);
+            }
+        }
+
+        if let Some(group) = self.groups.last_mut() {
+            group.count = self.indices.len() - group.start;
+        }
+    }
+
+    /// Moves the center of the bounding box to the origin and scales it uniformly to fit into `-1` to `1`
+    pub fn center_and_scale(&mut self) {
+        if self.positions.is_empty() {
+            return;
+        }
+
+        let mut min = self.positions[0];
+        let mut max = self.positions[0];
+        for p in self.positions.iter() {
+            min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        let center = (min + max) * 0.5;
+        let extent = max - min;
+        let largest = extent.x.max(extent.y).max(extent.z);
+        let scale = if largest > 0. { 2. / largest } else { 1. };
+        for p in self.positions.iter_mut() {
+            *p = (*p - center) * scale;
+        }
+    }
+
+    /// In local space, `None` if there are no vertices
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions.iter())
+    }
+
+    /// Adds the vertices and groups of `other`, transformed by `model`.
+    /// The group materials stay the same, so both meshes have to use the same materials.
+    pub fn append(&mut self, other: &Mesh, model: &Matrix4) {
+        let offset = self.positions.len();
+        let count = other.positions.len();
+        let normal_matrix = model
+            .to_matrix3()
+            .inverse()
+            .map_or(Matrix3::identity(), |inverse| inverse.transpose());
+
+        self.positions
+            .extend(other.positions.iter().map(|p| model.transform_point(p)));
+        let normals: Vec<Vector3> = other
+            .normals
+            .iter()
+            .map(|n| normal_matrix.transform(n).normalized())
+            .collect();
+        append_attribute(&mut self.normals, offset, &normals, count, Vector3::zero());
+        append_attribute(
+            &mut self.uvs,
+            offset,
+            &other.uvs,
+            count,
+            Vector2::new(0., 0.),
+        );
+        append_attribute(
+            &mut self.colors,
+            offset,
+            &other.colors,
+            count,
+            RgbColor::WHITE,
+        );
+
+        let start = self.indices.len();
+        let mirrored = is_mirrored(model);
+        for triangle in other.indices.chunks_exact(3) {
+            let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+            let triangle = if mirrored { [a, c, b] } else { [a, b, c] };
+            self.indices
+                .extend(triangle.iter().map(|index| offset as u32 + index));
+        }
+        self.groups
+            .extend(other.groups.iter().map(|group| MeshGroup {
+                start: start + group.start,
+                ..*group
+            }));
+    }
+
+    /// The normal is zero if there is none
+    fn get_vertex(
+        &self,
+        index: u32,
+        world_positions: &[Vector3],
+        world_normals: &[Vector3],
+    ) -> Vertex {
+        let index = index as usize;
+        let color = self.colors.get(index).copied().unwrap_or(RgbColor::WHITE);
+        Vertex {
+            position: world_positions[index],
+            uv: self.uvs.get(index).copied().unwrap_or(Vector2::new(0., 0.)),
+            color,
+            shadow_color: color,
+            specular: RgbColor::BLACK,
+            normal: world_normals.get(index).copied().unwrap_or(Vector3::zero()),
+        }
+    }
+
+    /// Adds every triangle to the shadow map, `model` transforms to world space
+    pub fn render_shadow(&self, shadow_map: &mut ShadowMap, model: &Matrix4) {
+        let world_positions: Vec<Vector3> = self
+            .positions
+            .iter()
+            .map(|p| model.transform_point(p))
+            .collect();
+        for indices in self.indices.chunks_exact(3) {
+            shadow_map.render_triangle(&[
+                world_positions[indices[0] as usize],
+                world_positions[indices[1] as usize],
+                world_positions[indices[2] as usize],
+            ]);
+        }
+    }
+
+    pub fn render(
+        &self,
+        draw_buffer: &mut DrawBuffer,
+        context: &RenderContext,
+        model: &Matrix4,
+        materials: &[Material],
+    ) {
+        let fallback_material = Material::default();
+        let world_positions: Vec<Vector3> = self
+            .positions
+            .iter()
+            .map(|p| model.transform_point(p))
+            .collect();
+        // Stay perpendicular to the surface under non-uniform scaling
+        let normal_matrix = model
+            .to_matrix3()
+            .inverse()
+            .map_or(Matrix3::identity(), |inverse| inverse.transpose());
+        let world_normals: Vec<Vector3> = self
+            .normals
+            .iter()
+            .map(|n| normal_matrix.transform(n).normalized())
+            .collect();
+
+        // Mirroring turns the triangles around, which would swap their front and back
+        let (second, third) = if is_mirrored(model) { (2, 1) } else { (1, 2) };
+
+        let eye = context.camera.position;
+        for group in self.groups.iter() {
+            let material = materials.get(group.material).unwrap_or(&fallback_material);
+
+            for indices in self.indices[group.start..group.start + group.count].chunks_exact(3) {
+                let mut triangle = Triangle3D {
+                    vertices: [
+                        self.get_vertex(indices[0], &world_positions, &world_normals),
+                        self.get_vertex(indices[second], &world_positions, &world_normals),
+                        self.get_vertex(indices[third], &world_positions, &world_normals),
+                    ],
+                };
+
+                // From the camera to the triangle, `scaled_normal` points to the back
+                let view = triangle.vertices[0].position - eye;
+                let back_facing = view.dot(&triangle.scaled_normal()) < 0.;
+                let culled = match material.cull_mode {
+                    CullMode::Back => back_facing,
+                    CullMode::Front => !back_facing,
+                    CullMode::None => false,
+                };
+                if culled {
+                    continue;
+                }
+
+                // The back side is lit as if it was the front
+                let face_normal = if back_facing {
+                    triangle.normal() * -1.
+                } else {
+                    triangle.normal()
+                };
+                for vertex in triangle.vertices.iter_mut() {
+                    if vertex.normal.length() == 0. {
+                        vertex.normal = face_normal;
+                    } else if back_facing {
+                        vertex.normal = vertex.normal * -1.;
+                    }
+                }
+
+                match context.shading(material) {
+                    // Phong shading lights every pixel when the triangle gets rendered
+                    Shading::Unlit | Shading::Phong => {}
+                    Shading::Flat => {
+                        let lighting = context.light(material, &triangle.center(), &face_normal);
+                        for vertex in triangle.vertices.iter_mut() {
+                            vertex.shadow_color = vertex.color.multiply(&lighting.shadowed_diffuse);
+                            vertex.color = vertex.color.multiply(&lighting.diffuse);
+                            vertex.specular = lighting.specular;
+                        }
+                    }
+                    Shading::Gouraud => {
+                        for vertex in triangle.vertices.iter_mut() {
+                            let lighting =
+                                context.light(material, &vertex.position, &vertex.normal);
+                            vertex.shadow_color = vertex.color.multiply(&lighting.shadowed_diffuse);
+                            vertex.color = vertex.color.multiply(&lighting.diffuse);
+                            vertex.specular = lighting.specular;
+                        }
+                    }
+                }
+
+                triangle.render(draw_buffer, context, material);
+            }
+        }
+    }
+}
+
+fn is_mirrored(model: &Matrix4) -> bool {
+    model.to_matrix3().determinant() < 0.
+}
+
+/// Keeps `attribute` either empty or at one value per position when vertices are added after `offset`
+fn append_attribute<T: Copy>(
+    attribute: &mut Vec<T>,
+    offset: usize,
+    values: &[T],
+    count: usize,
+    default: T,
+) {
+    if attribute.is_empty() && values.is_empty() {
+        return;
+    }
+    attribute.resize(offset, default);
+    attribute.extend_from_slice(values);
+    attribute.resize(offset + count, default);
+}
+
+/// Two triangles for every cell of a grid with `columns + 1` vertices per row
+fn grid_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = row * (columns + 1) + column;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + columns + 1;
+            let bottom_right = bottom_left + 1;
+            indices.extend_from_slice(&[top_left, bottom_right, top_right]);
+            indices.extend_from_slice(&[top_left, bottom_left, bottom_right]);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        Mesh::new(
+            vec![
+                Vector3::new(0., 0., 0.),
+                Vector3::new(1., 0., 0.),
+                Vector3::new(0., 1., 0.),
+            ],
+            Vec::new(),
+            vec![Vector2::new(0., 0.); 3],
+            vec![0, 1, 2],
+        )
+    }
+
+    #[test]
+    fn append_transforms_and_offsets() {
+        let mut mesh = triangle();
+        mesh.append(
+            &triangle(),
+            &Matrix4::translation(&Vector3::new(0., 0., 2.)),
+        );
+
+        assert_eq!(mesh.positions.len(), 6);
+        assert_eq!(mesh.positions[4].z, 2.);
+        assert_eq!(mesh.indices, [0, 1, 2, 3, 4, 5]);
+        let groups: Vec<(usize, usize)> = mesh.groups.iter().map(|g| (g.start, g.count)).collect();
+        assert_eq!(groups, [(0, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn append_keeps_the_front_of_mirrored_triangles() {
+        let mut mesh = Mesh::empty();
+        mesh.append(&triangle(), &Matrix4::scale(&Vector3::new(-1., 1., 1.)));
+        assert_eq!(mesh.indices, [0, 2, 1]);
+    }
+
+    #[test]
+    fn append_fills_missing_attributes() {
+        let mut mesh = Mesh::empty();
+        mesh.append(&triangle(), &Matrix4::identity());
+        let mut other = triangle();
+        other.uvs.clear();
+        other.colors = vec![RgbColor::BLACK; 3];
+        mesh.append(&other, &Matrix4::identity());
+
+        assert!(mesh.normals.is_empty());
+        assert_eq!(mesh.uvs.len(), 6);
+        assert_eq!(mesh.colors.len(), 6);
+        assert_eq!(mesh.colors[0].r, 255);
+        assert_eq!(mesh.colors[5].r, 0);
+    }
+}
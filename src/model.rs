@@ -0,0 +1,118 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::bounds::Aabb;
+use crate::cube::Cube;
+use crate::gltf_loader;
+use crate::material::Material;
+use crate::matrix4::Matrix4;
+use crate::mesh::Mesh;
+use crate::obj;
+use crate::ply;
+use crate::render_context::RenderContext;
+use crate::stl;
+use crate::terminal_renderer::DrawBuffer;
+
+/// A mesh together with the materials that its groups refer to
+#[derive(Clone)]
+pub struct Model {
+    /// Private, so that `bounds` always matches it
+    mesh: Mesh,
+    pub materials: Vec<Material>,
+    /// Problems that didn't stop the model from loading, like missing textures
+    pub warnings: Vec<String>,
+    /// Of the mesh, which can't change after the model is created
+    bounds: Option<Aabb>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Image(image::ImageError),
+    /// `line` starts at `1`, or is `0` for binary files
+    Parse {
+        line: usize,
+        message: String,
+    },
+    UnsupportedFormat(String),
+    /// Error in a file that another file refers to
+    InFile(PathBuf, Box<LoadError>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "{}", error),
+            LoadError::Image(error) => write!(f, "{}", error),
+            LoadError::Parse { line: 0, message } => write!(f, "{}", message),
+            LoadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            LoadError::UnsupportedFormat(extension) => {
+                write!(f, "unsupported file format '{}'", extension)
+            }
+            LoadError::InFile(path, error) => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for LoadError {
+    fn from(error: image::ImageError) -> Self {
+        LoadError::Image(error)
+    }
+}
+
+impl From<&Cube> for Model {
+    fn from(cube: &Cube) -> Self {
+        Model::new(cube.to_mesh(), cube.materials.as_slice().to_vec())
+    }
+}
+
+impl Model {
+    pub fn new(mesh: Mesh, materials: Vec<Material>) -> Model {
+        Model {
+            bounds: mesh.bounds(),
+            mesh,
+            materials,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// In local space, `None` if the mesh is empty
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    /// Picks the loader based on the file extension
+    pub fn load(path: &Path) -> Result<Model, LoadError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "obj" => obj::load(path),
+            "gltf" | "glb" => gltf_loader::load(path),
+            "stl" => stl::load(path),
+            "ply" => ply::load(path),
+            _ => Err(LoadError::UnsupportedFormat(extension)),
+        }
+    }
+
+    pub fn render(&self, draw_buffer: &mut DrawBuffer, context: &RenderContext, model: &Matrix4) {
+        self.mesh
+            .render(draw_buffer, context, model, &self.materials);
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::SplitWhitespace;
+
+use crate::material::{Material, Shading};
+use crate::mesh::{Mesh, MeshGroup};
+use crate::model::{LoadError, Model};
+use crate::terminal_renderer::RgbColor;
+use crate::texture::Texture;
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// Loads the model and all materials that it references.
+///
+/// OBJ files are Y-up with the viewer looking down -Z, so the Y and Z axes get flipped.
+/// Polygons are triangulated as fans.
+/// Material libraries and textures that can't be found become warnings, those materials stay untextured.
+pub fn load(path: &Path) -> Result<Model, LoadError> {
+    let source = fs::read_to_string(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+
+    let mut mesh = Mesh::empty();
+    let mut has_normals = false;
+    let mut has_uvs = false;
+    // Every distinct combination of position, texture coordinate and normal becomes a vertex
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    let mut library = MaterialLibrary::new();
+    let mut material = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| LoadError::Parse {
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => positions.push(flip(parse_vector3(&mut tokens).map_err(error)?)),
+            Some("vn") => normals.push(flip(parse_vector3(&mut tokens).map_err(error)?)),
+            Some("vt") => {
+                let u = parse_f64(tokens.next()).map_err(error)?;
+                let v = parse_f64(tokens.next().or(Some("0"))).map_err(error)?;
+                // OBJ texture coordinates start at the bottom left, textures at the top left
+                uvs.push(Vector2::new(u, 1. - v));
+            }
+            Some("f") => {
+                let mut face = Vec::new();
+                for token in tokens {
+                    let mut indices = token.split('/');
+                    let position = parse_index(indices.next(), positions.len()).map_err(error)?;
+                    let uv = parse_optional_index(indices.next(), uvs.len()).map_err(error)?;
+                    let normal =
+                        parse_optional_index(indices.next(), normals.len()).map_err(error)?;
+
+                    let vertex = *vertices.entry((position, uv, normal)).or_insert_with(|| {
+                        mesh.positions.push(positions[position]);
+                        mesh.uvs.push(uv.map_or(Vector2::new(0., 0.), |uv| uvs[uv]));
+                        mesh.normals
+                            .push(normal.map_or(Vector3::zero(), |normal| normals[normal]));
+                        (mesh.positions.len() - 1) as u32
+                    });
+                    has_uvs |= uv.is_some();
+                    has_normals |= normal.is_some();
+                    face.push(vertex);
+                }
+                if face.len() < 3 {
+                    return Err(error("a face needs at least three vertices".to_string()));
+                }
+
+                let material = match material {
+                    Some(material) => material,
+                    None => *material.insert(library.get_or_default("")),
+                };
+                start_group(&mut mesh, material);
+                for i in 1..face.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            Some("usemtl") => {
+                material = Some(library.get_or_default(rest_of_line(line, "usemtl")));
+            }
+            // Can list several files
+            Some("mtllib") => {
+                for file_name in tokens {
+                    library.load(&directory.join(file_name))?;
+                }
+            }
+            // Comments, objects, smoothing groups and everything else that we don't need
+            _ => {}
+        }
+    }
+
+    if let Some(group) = mesh.groups.last_mut() {
+        group.count = mesh.indices.len() - group.start;
+    }
+    if has_normals {
+        for material in library.materials.iter_mut() {
+            material.shading = Shading::Gouraud;
+        }
+    } else {
+        mesh.normals.clear();
+    }
+    if !has_uvs {
+        mesh.uvs.clear();
+    }
+
+    let mut model = Model::new(mesh, library.materials);
+    model.warnings = library.warnings;
+    Ok(model)
+}
+
+/// Starts a new group if the material changed
+fn start_group(mesh: &mut Mesh, material: usize) {
+    let start = mesh.indices.len();
+    if let Some(group) = mesh.groups.last_mut() {
+        if group.material == material {
+            return;
+        }
+        group.count = start - group.start;
+    }
+    mesh.groups.push(MeshGroup {
+        start,
+        count: 0,
+        material,
+    });
+}
+
+struct MaterialLibrary {
+    materials: Vec<Material>,
+    indices: HashMap<String, usize>,
+    /// Materials often share textures
+    textures: HashMap<PathBuf, Rc<Texture>>,
+    warnings: Vec<String>,
+}
+
+impl MaterialLibrary {
+    fn new() -> MaterialLibrary {
+        MaterialLibrary {
+            materials: Vec::new(),
+            indices: HashMap::new(),
+            textures: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Unknown materials are white
+    fn get_or_default(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.indices.get(name) {
+            return index;
+        }
+        self.materials.push(Material::default());
+        self.indices
+            .insert(name.to_string(), self.materials.len() - 1);
+        self.materials.len() - 1
+    }
+
+    /// A missing file only adds a warning, the materials that it would define stay white
+    fn load(&mut self, path: &Path) -> Result<(), LoadError> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                self.warnings.push(format!("{}: {}", path.display(), error));
+                return Ok(());
+            }
+        };
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut current = None;
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| LoadError::Parse {
+                line: line_number,
+                message: format!("{}: {}", path.display(), message),
+            };
+
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next();
+            if keyword == Some("newmtl") {
+                current = Some(self.get_or_default(rest_of_line(line, "newmtl")));
+                continue;
+            }
+            let material = match current {
+                Some(index) => &mut self.materials[index],
+                None => continue,
+            };
+
+            match keyword {
+                Some("Kd") => {
+                    let color = parse_vector3(&mut tokens).map_err(error)?;
+                    material.base_color = RgbColor::from_f64(color.x, color.y, color.z);
+                }
+                Some("Ks") => {
+                    let color = parse_vector3(&mut tokens).map_err(error)?;
+                    material.specular = RgbColor::from_f64(color.x, color.y, color.z);
+                }
+                Some("Ns") => material.shininess = parse_f64(tokens.next()).map_err(error)?,
+                Some("map_Kd") => {
+                    let file_name = texture_file_name(rest_of_line(line, "map_Kd"));
+                    if file_name.is_empty() {
+                        return Err(error("missing texture file name".to_string()));
+                    }
+                    let texture_path = directory.join(file_name.replace('\\', "/"));
+                    let texture = match self.textures.get(&texture_path) {
+                        Some(texture) => texture.clone(),
+                        None => match image::open(&texture_path) {
+                            Ok(image) => {
+                                let texture = Rc::new(Texture::new(image));
+                                self.textures.insert(texture_path, texture.clone());
+                                texture
+                            }
+                            Err(error) => {
+                                self.warnings.push(format!(
+                                    "{}: {}",
+                                    texture_path.display(),
+                                    error
+                                ));
+                                continue;
+                            }
+                        },
+                    };
+                    material.texture = Some(texture);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// From the Y-up coordinate system of OBJ files, this is a rotation so the winding order stays the same
+fn flip(v: Vector3) -> Vector3 {
+    Vector3::new(v.x, -v.y, -v.z)
+}
+
+/// Everything after the keyword, names and paths can contain spaces
+fn rest_of_line<'a>(line: &'a str, keyword: &str) -> &'a str {
+    line.trim_start()[keyword.len()..].trim()
+}
+
+/// Skips the options in front of a texture file name, like `-s 2 2 1 -clamp on`
+fn texture_file_name(arguments: &str) -> &str {
+    let mut rest = arguments.trim_start();
+    while let Some(option) = rest.strip_prefix('-') {
+        let (name, after) = next_token(option);
+        rest = after;
+        let (count, numbers_only) = match name {
+            // The last two numbers are optional
+            "o" | "s" | "t" => (3, true),
+            "mm" => (2, true),
+            _ => (1, false),
+        };
+        for _ in 0..count {
+            let (token, after) = next_token(rest);
+            if numbers_only && token.parse::<f64>().is_err() {
+                break;
+            }
+            rest = after;
+        }
+    }
+    rest.trim()
+}
+
+/// The first word and everything after it
+fn next_token(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+fn parse_f64(token: Option<&str>) -> Result<f64, String> {
+    let token = token.ok_or_else(|| "expected a number".to_string())?;
+    token
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", token))
+}
+
+fn parse_vector3(tokens: &mut SplitWhitespace) -> Result<Vector3, String> {
+    Ok(Vector3::new(
+        parse_f64(tokens.next())?,
+        parse_f64(tokens.next())?,
+        parse_f64(tokens.next())?,
+    ))
+}
+
+/// OBJ indices start at `1`, negative ones count backwards from the end
+fn parse_index(token: Option<&str>, len: usize) -> Result<usize, String> {
+    let token = token.ok_or_else(|| "expected an index".to_string())?;
+    let index: i64 = token
+        .parse()
+        .map_err(|_| format!("'{}' is not an index", token))?;
+
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!("index {} is out of range", index));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_optional_index(token: Option<&str>, len: usize) -> Result<Option<usize>, String> {
+    match token {
+        None | Some("") => Ok(None),
+        token => parse_index(token, len).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+    use crate::texture::{FilterMode, MipmapMode, Sampler};
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    fn load_model(path: &Path) -> Model {
+        load(path).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    #[test]
+    fn texture_coordinates_start_at_the_bottom_left() {
+        let directory = TestDirectory::new("obj-uvs");
+        // Red, green on top and blue, white at the bottom
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let image = RgbImage::from_fn(2, 2, |x, y| Rgb(colors[(y * 2 + x) as usize]));
+        DynamicImage::ImageRgb8(image)
+            .save(directory.join("my texture.png"))
+            .unwrap();
+        fs::write(
+            directory.join("model.mtl"),
+            "newmtl textured\nmap_Kd -s 1 1 1 -clamp on my texture.png\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.join("model.obj"),
+            "mtllib model.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             vt 0.25 0.25\nvt 0.75 0.25\nvt 0.25 0.75\n\
+             usemtl textured\nf 1/1 2/2 3/3\n",
+        )
+        .unwrap();
+
+        let model = load_model(&directory.join("model.obj"));
+        assert!(model.warnings.is_empty());
+        let texture = model.materials[0].texture.as_ref().unwrap();
+        let sampler = Sampler {
+            filter: FilterMode::Nearest,
+            mipmap_mode: MipmapMode::None,
+            ..Sampler::default()
+        };
+        let texels: Vec<(u8, u8, u8)> = model
+            .mesh()
+            .uvs
+            .iter()
+            .map(|uv| sampler.sample(texture, uv))
+            .map(|color| (color.r, color.g, color.b))
+            .collect();
+        assert_eq!(texels, [(0, 0, 255), (255, 255, 255), (255, 0, 0)]);
+    }
+
+    #[test]
+    fn missing_files_become_warnings() {
+        let directory = TestDirectory::new("obj-missing");
+        fs::write(directory.join("present.mtl"), "newmtl a\nmap_Kd nope.png\n").unwrap();
+        fs::write(
+            directory.join("model.obj"),
+            "mtllib missing.mtl\nmtllib present.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl a\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let model = load_model(&directory.join("model.obj"));
+        assert_eq!(model.warnings.len(), 2);
+        assert!(model.warnings[0].contains("missing.mtl"));
+        assert!(model.warnings[1].contains("nope.png"));
+        assert!(model.materials[0].texture.is_none());
+    }
+
+    #[test]
+    fn mtllib_loads_every_listed_file() {
+        let directory = TestDirectory::new("obj-mtllib");
+        fs::write(directory.join("a.mtl"), "newmtl a\nKd 1 0 0\n").unwrap();
+        fs::write(directory.join("b.mtl"), "newmtl b\nKd 0 1 0\n").unwrap();
+        fs::write(
+            directory.join("model.obj"),
+            "mtllib a.mtl  missing.mtl\tb.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl a\nf 1 2 3\nusemtl b\nf 1 3 2\n",
+        )
+        .unwrap();
+
+        let model = load_model(&directory.join("model.obj"));
+        assert_eq!(model.warnings.len(), 1);
+        assert!(model.warnings[0].contains("missing.mtl"));
+        let colors: Vec<(u8, u8, u8)> = model
+            .materials
+            .iter()
+            .map(|material| {
+                let color = material.base_color;
+                (color.r, color.g, color.b)
+            })
+            .collect();
+        assert_eq!(colors, [(255, 0, 0), (0, 255, 0)]);
+    }
+
+    #[test]
+    fn indices_out_of_range_are_errors() {
+        let directory = TestDirectory::new("obj-indices");
+        for face in ["f 1 2 4", "f 1/2 2/1 3/1", "f 0 1 2", "f -4 1 2"] {
+            let path = directory.join("model.obj");
+            fs::write(
+                &path,
+                format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n{}\n", face),
+            )
+            .unwrap();
+            assert!(
+                matches!(load(&path), Err(LoadError::Parse { line: 5, .. })),
+                "{}",
+                face
+            );
+        }
+    }
+
+    #[test]
+    fn texture_options_are_skipped() {
+        assert_eq!(texture_file_name("file.png"), "file.png");
+        assert_eq!(texture_file_name("-s 2 file name.png"), "file name.png");
+        assert_eq!(
+            texture_file_name("-clamp on -o 0.5 0.5 -bm 1 file.png"),
+            "file.png"
+        );
+        assert_eq!(texture_file_name("-imfchan r"), "");
+    }
+}
@@ -0,0 +1,440 @@
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::material::{Material, Shading};
+use crate::mesh::Mesh;
+use crate::model::{LoadError, Model};
+use crate::terminal_renderer::RgbColor;
+use crate::vector3::Vector3;
+
+#[derive(Copy, Clone)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Copy, Clone)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::I8),
+            "uchar" | "uint8" => Some(ScalarType::U8),
+            "short" | "int16" => Some(ScalarType::I16),
+            "ushort" | "uint16" => Some(ScalarType::U16),
+            "int" | "int32" => Some(ScalarType::I32),
+            "uint" | "uint32" => Some(ScalarType::U32),
+            "float" | "float32" => Some(ScalarType::F32),
+            "double" | "float64" => Some(ScalarType::F64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+enum PropertyType {
+    Scalar(ScalarType),
+    /// Type of the length and type of the items
+    List(ScalarType, ScalarType),
+}
+
+struct Property {
+    name: String,
+    property_type: PropertyType,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The data after the header
+enum Body<'a> {
+    /// Tokens with their line numbers
+    Ascii(Box<dyn Iterator<Item = (usize, &'a str)> + 'a>),
+    Binary {
+        bytes: &'a [u8],
+        offset: usize,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64, LoadError> {
+        match self {
+            Body::Ascii(tokens) => match tokens.next() {
+                Some((line, token)) => token.parse().map_err(|_| LoadError::Parse {
+                    line,
+                    message: format!("'{}' is not a number", token),
+                }),
+                None => Err(unexpected_end()),
+            },
+            Body::Binary { bytes, offset } => {
+                let size = scalar_type.size();
+                let value = bytes
+                    .get(*offset..*offset + size)
+                    .ok_or_else(unexpected_end)?;
+                *offset += size;
+                Ok(match scalar_type {
+                    ScalarType::I8 => value[0] as i8 as f64,
+                    ScalarType::U8 => value[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::U16 => u16::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::I32 => i32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::U32 => u32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::F32 => f32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(value.try_into().unwrap()),
+                })
+            }
+        }
+    }
+}
+
+fn unexpected_end() -> LoadError {
+    LoadError::Parse {
+        line: 0,
+        message: "unexpected end of file".to_string(),
+    }
+}
+
+/// Loads an ASCII or binary little-endian PLY file with optional vertex colors, centered and scaled to fit into `-1` to `1`.
+///
+/// PLY files are usually Y-up with the viewer looking down -Z, so the Y and Z axes get flipped.
+/// Polygons are triangulated as fans.
+pub fn load(path: &Path) -> Result<Model, LoadError> {
+    let bytes = fs::read(path)?;
+
+    let header_end = find(&bytes, b"end_header")
+        .and_then(|start| find(&bytes[start..], b"\n").map(|end| start + end + 1))
+        .ok_or_else(|| LoadError::Parse {
+            line: 0,
+            message: "missing end_header".to_string(),
+        })?;
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+    let (format, elements) = parse_header(&header)?;
+
+    let mut body = match format {
+        Format::Ascii => {
+            let header_lines = header.lines().count();
+            let source =
+                std::str::from_utf8(&bytes[header_end..]).map_err(|_| LoadError::Parse {
+                    line: 0,
+                    message: "invalid text in the data".to_string(),
+                })?;
+            Body::Ascii(Box::new(source.lines().enumerate().flat_map(
+                move |(index, line)| {
+                    line.split_whitespace()
+                        .map(move |token| (header_lines + index + 1, token))
+                },
+            )))
+        }
+        Format::BinaryLittleEndian => Body::Binary {
+            bytes: &bytes[header_end..],
+            offset: 0,
+        },
+    };
+
+    let mut mesh = Mesh::new(Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut has_colors = false;
+    for element in elements.iter() {
+        match element.name.as_str() {
+            "vertex" => has_colors = read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    read_values(&mut body, element)?;
+                }
+            }
+        }
+    }
+
+    if mesh.indices.is_empty() {
+        return Err(LoadError::Parse {
+            line: 0,
+            message: "the file has no faces".to_string(),
+        });
+    }
+    // The faces can come before the vertices
+    if mesh
+        .indices
+        .iter()
+        .any(|&index| index as usize >= mesh.positions.len())
+    {
+        return Err(face_index_error());
+    }
+    if !has_colors {
+        mesh.colors.clear();
+    }
+    mesh.groups[0].count = mesh.indices.len();
+    mesh.center_and_scale();
+
+    Ok(Model::new(
+        mesh,
+        vec![Material {
+            shading: Shading::Flat,
+            ..Material::default()
+        }],
+    ))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), LoadError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (index, line) in header.lines().enumerate() {
+        let error = |message: &str| LoadError::Parse {
+            line: index + 1,
+            message: message.to_string(),
+        };
+        let scalar_type =
+            |name: &str| ScalarType::parse(name).ok_or_else(|| error("unknown property type"));
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if index == 0 => {}
+            _ if index == 0 => return Err(error("not a PLY file")),
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", ..] => {
+                return Err(error("only ASCII and binary little-endian are supported"))
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error("invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    property_type: PropertyType::List(
+                        scalar_type(count_type)?,
+                        scalar_type(item_type)?,
+                    ),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| error("property outside of an element"))?
+                    .properties
+                    .push(property);
+            }
+            ["property", property_type, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    property_type: PropertyType::Scalar(scalar_type(property_type)?),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| error("property outside of an element"))?
+                    .properties
+                    .push(property);
+            }
+            // Comments, "obj_info" and "end_header"
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| LoadError::Parse {
+        line: 0,
+        message: "missing format".to_string(),
+    })?;
+    Ok((format, elements))
+}
+
+/// Every property of one element, lists are flattened
+fn read_values(body: &mut Body, element: &Element) -> Result<Vec<Vec<f64>>, LoadError> {
+    let mut values = Vec::with_capacity(element.properties.len());
+    for property in element.properties.iter() {
+        match property.property_type {
+            PropertyType::Scalar(scalar_type) => values.push(vec![body.read(scalar_type)?]),
+            PropertyType::List(count_type, item_type) => {
+                // Not trusted for the capacity, a corrupt count runs into the end of the file instead
+                let count = body.read(count_type)? as usize;
+                let mut items = Vec::new();
+                for _ in 0..count {
+                    items.push(body.read(item_type)?);
+                }
+                values.push(items);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Returns whether the vertices have colors
+fn read_vertices(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<bool, LoadError> {
+    let index_of = |name: &str| {
+        element
+            .properties
+            .iter()
+            .position(|property| property.name == name)
+    };
+    let (x, y, z) = match (index_of("x"), index_of("y"), index_of("z")) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => {
+            return Err(LoadError::Parse {
+                line: 0,
+                message: "vertices need x, y and z".to_string(),
+            })
+        }
+    };
+    let color = match (index_of("red"), index_of("green"), index_of("blue")) {
+        (Some(r), Some(g), Some(b)) => Some((r, g, b)),
+        _ => None,
+    };
+    // Colors are either bytes or in the 0 to 1 range
+    let color_scale = match color.map(|(r, _, _)| &element.properties[r].property_type) {
+        Some(PropertyType::Scalar(ScalarType::F32))
+        | Some(PropertyType::Scalar(ScalarType::F64)) => 1.,
+        _ => 1. / 255.,
+    };
+
+    for _ in 0..element.count {
+        let values = read_values(body, element)?;
+        let value = |index: usize| values[index].first().copied().unwrap_or(0.);
+
+        // Y-up to Y-down, this is a rotation so the winding order stays the same
+        mesh.positions
+            .push(Vector3::new(value(x), -value(y), -value(z)));
+        mesh.colors.push(match color {
+            Some((r, g, b)) => RgbColor::from_f64(
+                value(r) * color_scale,
+                value(g) * color_scale,
+                value(b) * color_scale,
+            ),
+            None => RgbColor::WHITE,
+        });
+    }
+
+    Ok(color.is_some())
+}
+
+fn face_index_error() -> LoadError {
+    LoadError::Parse {
+        line: 0,
+        message: "face index out of range".to_string(),
+    }
+}
+
+/// The indices are checked against the vertices once all elements are read
+fn read_faces(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<(), LoadError> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|property| property.name == "vertex_indices" || property.name == "vertex_index")
+        .ok_or_else(|| LoadError::Parse {
+            line: 0,
+            message: "faces need vertex_indices".to_string(),
+        })?;
+
+    for _ in 0..element.count {
+        let values = read_values(body, element)?;
+        let face = &values[indices];
+        // Anything above the range of `u32` saturates, and is out of range later
+        if face.iter().any(|&index| index < 0.) {
+            return Err(face_index_error());
+        }
+
+        for i in 1..face.len().saturating_sub(1) {
+            mesh.indices
+                .extend_from_slice(&[face[0] as u32, face[i] as u32, face[i + 1] as u32]);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    const ASCII_HEADER: &str = "ply\nformat ascii 1.0\n\
+        element vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+        element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    #[test]
+    fn loads_ascii_faces() {
+        let directory = TestDirectory::new("ply-ascii");
+        let path = directory.join("model.ply");
+        fs::write(
+            &path,
+            format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n", ASCII_HEADER),
+        )
+        .unwrap();
+
+        let model = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(model.mesh().indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn faces_can_come_before_vertices() {
+        let directory = TestDirectory::new("ply-order");
+        let path = directory.join("model.ply");
+        fs::write(
+            &path,
+            "ply\nformat ascii 1.0\n\
+             element face 2\nproperty list uchar int vertex_indices\n\
+             element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+             end_header\n3 0 1 2\n3 0 2 3\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n",
+        )
+        .unwrap();
+
+        let model = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(model.mesh().positions.len(), 4);
+        assert_eq!(model.mesh().indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn indices_out_of_range_are_errors() {
+        let directory = TestDirectory::new("ply-indices");
+        let path = directory.join("model.ply");
+        for face in ["3 0 1 3", "3 0 -1 2", "3 0 1 4294967296"] {
+            fs::write(
+                &path,
+                format!("{}0 0 0\n1 0 0\n0 1 0\n{}\n", ASCII_HEADER, face),
+            )
+            .unwrap();
+            assert!(load(&path).is_err(), "{}", face);
+        }
+    }
+
+    #[test]
+    fn corrupt_list_counts_are_errors() {
+        let directory = TestDirectory::new("ply-list");
+        let path = directory.join("model.ply");
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\n\
+            element vertex 0\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uint int vertex_indices\nend_header\n"
+            .to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        assert!(load(&path).is_err());
+    }
+}
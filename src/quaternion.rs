@@ -0,0 +1,241 @@
+use crate::matrix3::Matrix3;
+use crate::matrix4::Matrix4;
+use crate::vector3::Vector3;
+
+/// Unit quaternion describing a rotation
+#[derive(Copy, Clone)]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::identity()
+    }
+}
+
+impl Quaternion {
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Quaternion {
+        Quaternion { x, y, z, w }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(0., 0., 0., 1.)
+    }
+
+    /// Rotates counterclockwise around the `axis` by `angle` degrees
+    pub fn from_axis_angle(axis: &Vector3, angle: f64) -> Quaternion {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle.to_radians() * 0.5).sin_cos();
+        Quaternion::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    /// Same convention as `Vector3::rotate_euler`
+    pub fn from_euler(euler_angles: &Vector3) -> Quaternion {
+        Quaternion::from_axis_angle(&Vector3::new(0., 0., 1.), euler_angles.z)
+            * Quaternion::from_axis_angle(&Vector3::new(0., 1., 0.), euler_angles.y)
+            * Quaternion::from_axis_angle(&Vector3::new(1., 0., 0.), euler_angles.x)
+    }
+
+    /// Inverse of `from_euler`, in degrees
+    pub fn to_euler(&self) -> Vector3 {
+        let m = self.to_matrix3().m;
+        let sin_y = (-m[2][0]).clamp(-1., 1.);
+        let y = sin_y.asin();
+
+        if sin_y.abs() < 1. - 1e-9 {
+            Vector3::new(
+                m[2][1].atan2(m[2][2]).to_degrees(),
+                y.to_degrees(),
+                m[1][0].atan2(m[0][0]).to_degrees(),
+            )
+        } else {
+            // Gimbal lock, the X and Z rotations are around the same axis
+            Vector3::new(
+                (sin_y * m[0][1]).atan2(m[1][1]).to_degrees(),
+                y.to_degrees(),
+                0.,
+            )
+        }
+    }
+
+    pub fn dot(&self, rhs: &Quaternion) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        if length > f64::EPSILON {
+            Quaternion::new(
+                self.x / length,
+                self.y / length,
+                self.z / length,
+                self.w / length,
+            )
+        } else {
+            Quaternion::identity()
+        }
+    }
+
+    /// The inverse rotation
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    /// Spherical linear interpolation along the shortest path
+    pub fn slerp(&self, rhs: &Quaternion, t: f64) -> Quaternion {
+        let mut cos_angle = self.dot(rhs);
+        let mut rhs = *rhs;
+        if cos_angle < 0. {
+            cos_angle = -cos_angle;
+            rhs = Quaternion::new(-rhs.x, -rhs.y, -rhs.z, -rhs.w);
+        }
+
+        let (a, b) = if cos_angle > 1. - 1e-6 {
+            // Nearly identical, fall back to a linear interpolation
+            (1. - t, t)
+        } else {
+            let angle = cos_angle.acos();
+            let sin_angle = angle.sin();
+            (
+                ((1. - t) * angle).sin() / sin_angle,
+                (t * angle).sin() / sin_angle,
+            )
+        };
+
+        Quaternion::new(
+            self.x * a + rhs.x * b,
+            self.y * a + rhs.y * b,
+            self.z * a + rhs.z * b,
+            self.w * a + rhs.w * b,
+        )
+        .normalized()
+    }
+
+    pub fn rotate(&self, v: &Vector3) -> Vector3 {
+        self.to_matrix3().transform(v)
+    }
+
+    pub fn to_matrix3(&self) -> Matrix3 {
+        let Quaternion { x, y, z, w } = *self;
+        Matrix3::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - z * w),
+                2. * (x * z + y * w),
+            ],
+            [
+                2. * (x * y + z * w),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - x * w),
+            ],
+            [
+                2. * (x * z - y * w),
+                2. * (y * z + x * w),
+                1. - 2. * (x * x + y * y),
+            ],
+        ])
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        self.to_matrix3().into()
+    }
+}
+
+/// Applies `rhs` first, then `self`
+impl std::ops::Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Quaternion::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    /// `q` and `-q` are the same rotation
+    fn assert_same_rotation(a: &Quaternion, b: &Quaternion) {
+        assert!(
+            (a.dot(b).abs() - 1.).abs() < EPSILON,
+            "({}, {}, {}, {}) != ({}, {}, {}, {})",
+            a.x,
+            a.y,
+            a.z,
+            a.w,
+            b.x,
+            b.y,
+            b.z,
+            b.w
+        );
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        for (x, y, z) in [
+            (0., 0., 0.),
+            (30., -45., 60.),
+            (-170., 80., 10.),
+            (90., 10., -90.),
+        ] {
+            let euler = Quaternion::from_euler(&Vector3::new(x, y, z)).to_euler();
+            assert!((euler.x - x).abs() < 1e-6, "{} != {}", euler.x, x);
+            assert!((euler.y - y).abs() < 1e-6, "{} != {}", euler.y, y);
+            assert!((euler.z - z).abs() < 1e-6, "{} != {}", euler.z, z);
+        }
+    }
+
+    #[test]
+    fn euler_angles_at_gimbal_lock_keep_the_rotation() {
+        let rotation = Quaternion::from_euler(&Vector3::new(20., 90., 30.));
+        assert_same_rotation(&Quaternion::from_euler(&rotation.to_euler()), &rotation);
+    }
+
+    #[test]
+    fn matches_euler_matrix() {
+        let angles = Vector3::new(30., -45., 60.);
+        let v = Vector3::new(1., 2., 3.);
+        let a = Quaternion::from_euler(&angles).rotate(&v);
+        let b = v.rotate_euler(&angles);
+        assert!((a - b).length() < EPSILON);
+    }
+
+    #[test]
+    fn slerp_endpoints() {
+        let a = Quaternion::from_euler(&Vector3::new(10., 20., 30.));
+        let b = Quaternion::from_euler(&Vector3::new(-40., 50., 5.));
+        assert_same_rotation(&a.slerp(&b, 0.), &a);
+        assert_same_rotation(&a.slerp(&b, 1.), &b);
+    }
+
+    #[test]
+    fn slerp_halfway() {
+        let axis = Vector3::new(0., 1., 0.);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(&axis, 90.);
+        assert_same_rotation(&a.slerp(&b, 0.5), &Quaternion::from_axis_angle(&axis, 45.));
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_path() {
+        let axis = Vector3::new(0., 0., 1.);
+        let a = Quaternion::from_axis_angle(&axis, 10.);
+        // -q is the same rotation as q, but on the other side of the sphere
+        let b = Quaternion::from_axis_angle(&axis, 30.);
+        let b = Quaternion::new(-b.x, -b.y, -b.z, -b.w);
+        let halfway = a.slerp(&b, 0.5);
+        assert_same_rotation(&halfway, &Quaternion::from_axis_angle(&axis, 20.));
+        assert!((halfway.dot(&halfway) - 1.).abs() < EPSILON);
+    }
+}
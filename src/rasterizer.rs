@@ -0,0 +1,273 @@
+use crate::vector2::Vector2;
+
+/// Screen coordinates are snapped to 1/256th of a pixel
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_SCALE: f64 = (1 << SUBPIXEL_BITS) as f64;
+/// Keeps the fixed point math from overflowing, anything further out is off-screen anyways
+const MAX_COORDINATE: f64 = (1 << 20) as f64;
+
+pub struct Fragment {
+    pub column: u16,
+    pub row: u16,
+    /// Screen space weights of the three vertices, they add up to `1`
+    pub barycentric: [f64; 3],
+    /// How `barycentric` changes when going one pixel to the right
+    pub barycentric_dx: [f64; 3],
+    /// How `barycentric` changes when going one pixel down
+    pub barycentric_dy: [f64; 3],
+}
+
+/// Signed area of the parallelogram spanned by `a -> b` and `a -> p`, `A * (p.x - a.x) + B * (p.y - a.y)`
+#[derive(Copy, Clone)]
+struct EdgeFunction {
+    a: i64,
+    b: i64,
+    /// Value at the first pixel center of the bounding box
+    start: i64,
+    /// Keeps pixels exactly on a shared edge from being drawn by both triangles
+    bias: i64,
+}
+
+impl EdgeFunction {
+    fn new(from: (i64, i64), to: (i64, i64), start: (i64, i64)) -> EdgeFunction {
+        let a = from.1 - to.1;
+        let b = to.0 - from.0;
+
+        // Top-left fill rule, with the triangle interior on the positive side.
+        // A top edge is horizontal with the interior below it, a left edge goes upwards.
+        let is_top_edge = a == 0 && b > 0;
+        let is_left_edge = a > 0;
+
+        EdgeFunction {
+            a,
+            b,
+            start: a * (start.0 - from.0) + b * (start.1 - from.1),
+            bias: if is_top_edge || is_left_edge { 0 } else { -1 },
+        }
+    }
+}
+
+/// Calls `fragment` for every pixel whose center is inside of the triangle.
+///
+/// The vertices are in pixel coordinates and can have either winding order.
+/// Triangles that share an edge never draw the same pixel twice and never leave gaps.
+pub fn rasterize_triangle<F: FnMut(Fragment)>(
+    vertices: &[Vector2<f64>; 3],
+    width: u16,
+    height: u16,
+    mut fragment: F,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let to_fixed = |v: &Vector2<f64>| {
+        (
+            (v.x.clamp(-MAX_COORDINATE, MAX_COORDINATE) * SUBPIXEL_SCALE).round() as i64,
+            (v.y.clamp(-MAX_COORDINATE, MAX_COORDINATE) * SUBPIXEL_SCALE).round() as i64,
+        )
+    };
+    let mut fixed = [
+        to_fixed(&vertices[0]),
+        to_fixed(&vertices[1]),
+        to_fixed(&vertices[2]),
+    ];
+
+    // Make the winding consistent, and remember to undo it for the barycentric coordinates
+    let area = (fixed[1].0 - fixed[0].0) * (fixed[2].1 - fixed[0].1)
+        - (fixed[1].1 - fixed[0].1) * (fixed[2].0 - fixed[0].0);
+    if area == 0 {
+        return;
+    }
+    let swapped = area < 0;
+    if swapped {
+        fixed.swap(1, 2);
+    }
+    let area = area.abs() as f64;
+
+    // Bounding box, in pixels
+    let min_x = fixed.iter().map(|v| v.0).min().unwrap_or(0);
+    let max_x = fixed.iter().map(|v| v.0).max().unwrap_or(0);
+    let min_y = fixed.iter().map(|v| v.1).min().unwrap_or(0);
+    let max_y = fixed.iter().map(|v| v.1).max().unwrap_or(0);
+    let to_pixel = |v: i64| (v >> SUBPIXEL_BITS) as f64;
+    let min_column = to_pixel(min_x).clamp(0., (width - 1) as f64) as u16;
+    let max_column = to_pixel(max_x).clamp(0., (width - 1) as f64) as u16;
+    let min_row = to_pixel(min_y).clamp(0., (height - 1) as f64) as u16;
+    let max_row = to_pixel(max_y).clamp(0., (height - 1) as f64) as u16;
+
+    // Pixel centers are at half coordinates
+    let half_pixel = 1 << (SUBPIXEL_BITS - 1);
+    let start = (
+        ((min_column as i64) << SUBPIXEL_BITS) + half_pixel,
+        ((min_row as i64) << SUBPIXEL_BITS) + half_pixel,
+    );
+
+    // Each edge function is the weight of the vertex opposite to it
+    let edges = [
+        EdgeFunction::new(fixed[1], fixed[2], start),
+        EdgeFunction::new(fixed[2], fixed[0], start),
+        EdgeFunction::new(fixed[0], fixed[1], start),
+    ];
+
+    let mut barycentric_dx = edges.map(|e| (e.a << SUBPIXEL_BITS) as f64 / area);
+    let mut barycentric_dy = edges.map(|e| (e.b << SUBPIXEL_BITS) as f64 / area);
+    if swapped {
+        barycentric_dx.swap(1, 2);
+        barycentric_dy.swap(1, 2);
+    }
+
+    let mut row_start = [edges[0].start, edges[1].start, edges[2].start];
+    for row in min_row..=max_row {
+        let mut w = row_start;
+        for column in min_column..=max_column {
+            if w[0] + edges[0].bias >= 0 && w[1] + edges[1].bias >= 0 && w[2] + edges[2].bias >= 0 {
+                let mut barycentric = w.map(|v| v as f64 / area);
+                if swapped {
+                    barycentric.swap(1, 2);
+                }
+
+                fragment(Fragment {
+                    column,
+                    row,
+                    barycentric,
+                    barycentric_dx,
+                    barycentric_dy,
+                });
+            }
+
+            for i in 0..3 {
+                w[i] += edges[i].a << SUBPIXEL_BITS;
+            }
+        }
+
+        for i in 0..3 {
+            row_start[i] += edges[i].b << SUBPIXEL_BITS;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u16 = 16;
+
+    /// How often each pixel is drawn by the triangles, row by row
+    fn coverage(triangles: &[[(f64, f64); 3]]) -> Vec<u32> {
+        let mut counts = vec![0; SIZE as usize * SIZE as usize];
+        for triangle in triangles {
+            let vertices = triangle.map(|(x, y)| Vector2::new(x, y));
+            rasterize_triangle(&vertices, SIZE, SIZE, |fragment| {
+                counts[fragment.row as usize * SIZE as usize + fragment.column as usize] += 1;
+            });
+        }
+        counts
+    }
+
+    fn covered_pixels(counts: &[u32]) -> Vec<(u16, u16)> {
+        (0..counts.len())
+            .filter(|i| counts[*i] > 0)
+            .map(|i| ((i % SIZE as usize) as u16, (i / SIZE as usize) as u16))
+            .collect()
+    }
+
+    #[test]
+    fn shared_diagonal_is_drawn_once() {
+        // The diagonal goes through the pixel centers
+        let counts = coverage(&[
+            [(0., 0.), (8., 0.), (8., 8.)],
+            [(0., 0.), (8., 8.), (0., 8.)],
+        ]);
+        assert!(counts.iter().all(|count| *count <= 1));
+        assert_eq!(counts.iter().sum::<u32>(), 64);
+    }
+
+    #[test]
+    fn fan_is_watertight() {
+        // Triangles around an off-grid center, alternating windings
+        let center = (7.3, 6.9);
+        let outline = [
+            (1.5, 1.5),
+            (8.25, 0.5),
+            (14.5, 2.75),
+            (13.5, 13.5),
+            (6.5, 14.1),
+            (0.7, 9.5),
+        ];
+        let triangles: Vec<[(f64, f64); 3]> = (0..outline.len())
+            .map(|i| {
+                let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+                if i % 2 == 0 {
+                    [center, a, b]
+                } else {
+                    [center, b, a]
+                }
+            })
+            .collect();
+
+        let counts = coverage(&triangles);
+        assert!(counts.iter().all(|count| *count <= 1));
+        // No holes, the center of the fan is covered by exactly one of them
+        assert_eq!(counts[6 * SIZE as usize + 7], 1);
+        // Every pixel between two covered pixels in a row is covered too, the outline is convex
+        for row in 0..SIZE as usize {
+            let covered: Vec<usize> = (0..SIZE as usize)
+                .filter(|column| counts[row * SIZE as usize + column] > 0)
+                .collect();
+            if let (Some(first), Some(last)) = (covered.first(), covered.last()) {
+                assert_eq!(covered.len(), last - first + 1, "row {}", row);
+            }
+        }
+    }
+
+    #[test]
+    fn top_left_rule_on_pixel_centers() {
+        // All four edges go through pixel centers, only the top and left ones are drawn
+        let square = [
+            [(0.5, 0.5), (4.5, 0.5), (4.5, 4.5)],
+            [(0.5, 0.5), (4.5, 4.5), (0.5, 4.5)],
+        ];
+        let expected: Vec<(u16, u16)> = (0..4)
+            .flat_map(|row| (0..4).map(move |column| (column, row)))
+            .collect();
+
+        let counts = coverage(&square);
+        assert!(counts.iter().all(|count| *count <= 1));
+        assert_eq!(covered_pixels(&counts), expected);
+
+        // The winding doesn't matter
+        let reversed = square.map(|[a, b, c]| [a, c, b]);
+        assert_eq!(covered_pixels(&coverage(&reversed)), expected);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_empty() {
+        let counts = coverage(&[
+            // A point
+            [(3.5, 3.5), (3.5, 3.5), (3.5, 3.5)],
+            // On a line through pixel centers
+            [(0.5, 2.5), (5.5, 2.5), (10.5, 2.5)],
+            [(1.5, 1.5), (4.5, 4.5), (8.5, 8.5)],
+            // Smaller than the subpixel precision
+            [(5.5, 5.5), (5.5 + 1e-4, 5.5), (5.5, 5.5 + 1e-4)],
+        ]);
+        assert!(counts.iter().all(|count| *count == 0));
+    }
+
+    #[test]
+    fn barycentric_coordinates_add_up_to_one() {
+        let vertices = [
+            Vector2::new(1., 1.),
+            Vector2::new(12., 3.),
+            Vector2::new(4., 14.),
+        ];
+        let mut fragments = 0;
+        rasterize_triangle(&vertices, SIZE, SIZE, |fragment| {
+            fragments += 1;
+            assert!((fragment.barycentric.iter().sum::<f64>() - 1.).abs() < 1e-9);
+            assert!(fragment.barycentric.iter().all(|weight| *weight >= 0.));
+        });
+        assert!(fragments > 0);
+    }
+}
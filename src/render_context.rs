@@ -0,0 +1,97 @@
+use crate::camera::Camera;
+use crate::light::{self, Light, Lighting, DEFAULT_LIGHTS};
+use crate::material::{Material, Shading};
+use crate::shadow_map::ShadowMap;
+use crate::terminal_renderer::RgbColor;
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// What gets shown for every pixel
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Materials and vertex colors
+    Textured,
+    /// Texture coordinates as red and green, repeating outside of the `0` to `1` range
+    Uv,
+    /// Closer is brighter, see `DrawBuffer::show_depth`
+    Depth,
+}
+
+/// Everything that affects how a scene is drawn, apart from the target
+pub struct RenderContext<'a> {
+    pub camera: &'a Camera,
+    pub mode: RenderMode,
+    /// Only affects materials that aren't `Shading::Unlit`
+    pub lights: &'a [Light],
+    /// Replaces the shading of every material that isn't `Shading::Unlit`
+    pub shading: Option<Shading>,
+    /// One for every light that casts shadows, in the same order as the lights. Filled in by `Scene::render`.
+    pub shadow_maps: &'a [Option<ShadowMap>],
+}
+
+impl<'a> RenderContext<'a> {
+    pub fn new(camera: &'a Camera) -> RenderContext<'a> {
+        RenderContext {
+            camera,
+            mode: RenderMode::Textured,
+            lights: &DEFAULT_LIGHTS,
+            shading: None,
+            shadow_maps: &[],
+        }
+    }
+
+    /// Shading of the material, unless it is overridden
+    pub fn shading(&self, material: &Material) -> Shading {
+        match (material.shading, self.shading) {
+            (Shading::Unlit, _) | (_, None) => material.shading,
+            (_, Some(shading)) => shading,
+        }
+    }
+
+    /// Light reflected by a surface at `position` facing towards `normal`, both in world space
+    pub fn light(&self, material: &Material, position: &Vector3, normal: &Vector3) -> Lighting {
+        light::blinn_phong(
+            self.lights,
+            material,
+            position,
+            normal,
+            &self.camera.position,
+        )
+    }
+
+    /// `1` where the lights that cast shadows reach `position`, `0` in their shadows
+    pub fn shadow_visibility(&self, position: &Vector3, normal: &Vector3) -> f64 {
+        let mut sum = 0.;
+        let mut count = 0;
+        for shadow_map in self.shadow_maps.iter().flatten() {
+            sum += shadow_map.visibility(position, normal);
+            count += 1;
+        }
+        if count == 0 {
+            1.
+        } else {
+            sum / count as f64
+        }
+    }
+
+    /// `color` is the interpolated vertex color, `specular` gets added on top
+    pub fn shade(
+        &self,
+        material: &Material,
+        uv: &Vector2<f64>,
+        uv_dx: &Vector2<f64>,
+        uv_dy: &Vector2<f64>,
+        color: &RgbColor,
+        specular: &RgbColor,
+    ) -> RgbColor {
+        match self.mode {
+            RenderMode::Textured => material
+                .shade(uv, uv_dx, uv_dy)
+                .multiply(color)
+                .add(specular),
+            RenderMode::Uv => RgbColor::from_f64(uv.x.rem_euclid(1.), uv.y.rem_euclid(1.), 0.),
+            // Only the depth buffer matters
+            RenderMode::Depth => RgbColor::WHITE,
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::gltf_loader;
+use crate::light::{Light, DEFAULT_LIGHTS};
+use crate::matrix4::Matrix4;
+use crate::model::{LoadError, Model};
+use crate::quaternion::Quaternion;
+use crate::render_context::{RenderContext, RenderMode};
+use crate::shadow_map::{ShadowMap, SHADOW_MAP_SIZE};
+use crate::terminal_renderer::DrawBuffer;
+use crate::transform::Transform;
+use crate::vector3::Vector3;
+
+/// Part of a scene, the transform is relative to the parent node
+#[derive(Clone)]
+pub struct Node {
+    /// Used to find the node again, doesn't have to be unique
+    pub name: String,
+    pub pos: Vector3,
+    pub rotation: Quaternion,
+    pub size: Vector3,
+    /// Degrees per second around the local axes, applied to the `rotation` by `Scene::update`
+    pub spin: Vector3,
+    /// Models can be shared between nodes
+    pub renderable: Option<Rc<Model>>,
+    pub children: Vec<Node>,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node {
+            name: String::new(),
+            pos: Vector3::zero(),
+            rotation: Quaternion::identity(),
+            size: Vector3::one(),
+            spin: Vector3::zero(),
+            renderable: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl Node {
+    pub fn new(name: &str, renderable: Option<Rc<Model>>) -> Node {
+        Node {
+            name: name.to_string(),
+            renderable,
+            ..Node::default()
+        }
+    }
+
+    /// Loads a model file into a node without a transform.
+    ///
+    /// glTF files keep their node hierarchy as children, other files become the model of the node.
+    pub fn load(path: &Path) -> Result<Node, LoadError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "gltf" | "glb" => gltf_loader::load_scene(path),
+            _ => Ok(Node::new("", Some(Rc::new(Model::load(path)?)))),
+        }
+    }
+
+    /// Relative to the parent
+    pub fn transform(&self) -> Transform {
+        Transform {
+            position: self.pos,
+            rotation: self.rotation,
+            scale: self.size,
+        }
+    }
+
+    /// Depth-first search, including this node
+    pub fn find(&self, name: &str) -> Option<&Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(name))
+    }
+
+    /// Includes the children, models that are shared with other nodes get copied before they are changed
+    pub fn for_each_model_mut<F: FnMut(&mut Model)>(&mut self, f: &mut F) {
+        if let Some(model) = &mut self.renderable {
+            f(Rc::make_mut(model));
+        }
+        for child in self.children.iter_mut() {
+            child.for_each_model_mut(f);
+        }
+    }
+
+    fn update(&mut self, seconds: f64) {
+        let spin = Quaternion::from_euler(&(self.spin * seconds));
+        self.rotation = (self.rotation * spin).normalized();
+        for child in self.children.iter_mut() {
+            child.update(seconds);
+        }
+    }
+
+    fn visit<F: FnMut(&Node, &Matrix4)>(&self, parent: &Matrix4, f: &mut F) {
+        let world = *parent * self.transform().to_matrix();
+        f(self, &world);
+        for child in self.children.iter() {
+            child.visit(&world, f);
+        }
+    }
+}
+
+/// How many models `Scene::render` drew
+#[derive(Copy, Clone, Default)]
+pub struct RenderStats {
+    /// Nodes with a model
+    pub objects: usize,
+    /// Objects that were skipped because they are outside of the view
+    pub culled: usize,
+}
+
+/// Tree of nodes, the roots are in world space
+pub struct Scene {
+    pub nodes: Vec<Node>,
+    pub lights: Vec<Light>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            nodes: Vec::new(),
+            lights: DEFAULT_LIGHTS.to_vec(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn find(&self, name: &str) -> Option<&Node> {
+        self.nodes.iter().find_map(|node| node.find(name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.nodes.iter_mut().find_map(|node| node.find_mut(name))
+    }
+
+    /// Advances the animations
+    pub fn update(&mut self, seconds: f64) {
+        for node in self.nodes.iter_mut() {
+            node.update(seconds);
+        }
+    }
+
+    /// Walks the hierarchy, parents are visited before their children
+    pub fn for_each_world_transform<F: FnMut(&Node, &Matrix4)>(&self, mut f: F) {
+        for node in self.nodes.iter() {
+            node.visit(&Matrix4::identity(), &mut f);
+        }
+    }
+
+    /// Sphere around all models, `None` if there are none
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let mut bounds: Option<Aabb> = None;
+        self.for_each_world_transform(|node, world| {
+            if let Some(model_bounds) = node.renderable.as_ref().and_then(|model| model.bounds()) {
+                let model_bounds = model_bounds.transformed(world);
+                bounds = Some(match bounds {
+                    Some(bounds) => bounds.union(&model_bounds),
+                    None => model_bounds,
+                });
+            }
+        });
+
+        bounds.map(|bounds| {
+            let sphere = bounds.to_sphere();
+            BoundingSphere {
+                radius: sphere.radius.max(0.001),
+                ..sphere
+            }
+        })
+    }
+
+    /// Depth of the whole scene as seen from a directional light
+    fn render_shadow_map(&self, direction: &Vector3, center: &Vector3, radius: f64) -> ShadowMap {
+        let mut shadow_map = ShadowMap::new(direction, center, radius, SHADOW_MAP_SIZE);
+        self.for_each_world_transform(|node, world| {
+            if let Some(model) = &node.renderable {
+                model.mesh().render_shadow(&mut shadow_map, world);
+            }
+        });
+        shadow_map
+    }
+
+    /// Also renders the shadow maps of the lights in the `context`.
+    ///
+    /// Models outside of the view are skipped, but they can still cast shadows into it.
+    pub fn render(&self, draw_buffer: &mut DrawBuffer, context: &RenderContext) -> RenderStats {
+        let bounds = self.bounding_sphere();
+        let shadow_maps: Vec<Option<ShadowMap>> = context
+            .lights
+            .iter()
+            .map(|light| match (light, bounds) {
+                (
+                    Light::Directional {
+                        direction,
+                        shadows: true,
+                        ..
+                    },
+                    Some(sphere),
+                ) => Some(self.render_shadow_map(direction, &sphere.center, sphere.radius)),
+                _ => None,
+            })
+            .collect();
+        let context = &RenderContext {
+            shadow_maps: &shadow_maps,
+            ..*context
+        };
+
+        let frustum = context.camera.frustum();
+        let mut stats = RenderStats::default();
+        self.for_each_world_transform(|node, world| {
+            if let Some(model) = &node.renderable {
+                stats.objects += 1;
+                let visible = model
+                    .bounds()
+                    .is_some_and(|bounds| frustum.intersects(&bounds.transformed(world)));
+                if !visible {
+                    stats.culled += 1;
+                    return;
+                }
+                model.render(draw_buffer, context, world);
+            }
+        });
+
+        if context.mode == RenderMode::Depth {
+            draw_buffer.show_depth(context.camera);
+        }
+        stats
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::material::{CullMode, Material, Shading};
+use crate::mesh::Mesh;
+use crate::model::{LoadError, Model};
+use crate::quaternion::Quaternion;
+use crate::scene::{Node, Scene};
+use crate::terminal_renderer::RgbColor;
+use crate::texture::Texture;
+use crate::vector3::Vector3;
+
+/// JSON scene description, all paths are relative to the scene file.
+///
+/// ```json
+/// {
+///     "camera": { "position": [0, 0, -3], "fov": 40 },
+///     "lights": [
+///         { "type": "ambient", "intensity": 0.2 },
+///         { "type": "point", "position": [0, -2, -2], "color": [255, 200, 150], "range": 8 }
+///     ],
+///     "nodes": [
+///         {
+///             "name": "sun",
+///             "primitive": "cube",
+///             "size": [0.5, 0.5, 0.5],
+///             "spin": [0, 30, 0],
+///             "material": { "texture": "cat.png" },
+///             "children": [{ "model": "planet.obj", "pos": [3, 0, 0] }]
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: Option<CameraDescription>,
+    /// The default lights if there are none
+    lights: Option<Vec<LightDescription>>,
+    #[serde(default)]
+    nodes: Vec<NodeDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    #[serde(default = "default_camera_position")]
+    position: [f64; 3],
+    #[serde(default)]
+    euler_angles: [f64; 3],
+    /// Vertical, in degrees
+    #[serde(default = "default_fov")]
+    fov: f64,
+}
+
+/// Colors are `0` to `255`, positions and directions are in world space
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDescription {
+    Ambient {
+        #[serde(default = "default_light_color")]
+        color: [u8; 3],
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Directional {
+        /// Where the light travels to
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [u8; 3],
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        #[serde(default = "default_shadows")]
+        shadows: bool,
+    },
+    Point {
+        position: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [u8; 3],
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        /// Distance at which the light fades out completely
+        #[serde(default = "default_range")]
+        range: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeDescription {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    pos: [f64; 3],
+    #[serde(default)]
+    euler_angles: [f64; 3],
+    #[serde(default = "default_size")]
+    size: [f64; 3],
+    /// Degrees per second
+    #[serde(default)]
+    spin: [f64; 3],
+    /// Either a model file or a primitive, or neither for a group of nodes.
+    /// The nodes of glTF files are added in front of the `children`.
+    model: Option<PathBuf>,
+    primitive: Option<Primitive>,
+    /// Overrides the materials of the model
+    material: Option<MaterialDescription>,
+    #[serde(default)]
+    children: Vec<NodeDescription>,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Primitive {
+    Cube,
+    Plane,
+    UvSphere,
+    Icosphere,
+    Cylinder,
+    Cone,
+    Torus,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDescription {
+    /// `0` to `255`
+    color: Option<[u8; 3]>,
+    texture: Option<PathBuf>,
+    shading: Option<Shading>,
+    /// Color of the highlights, `0` to `255`
+    specular: Option<[u8; 3]>,
+    shininess: Option<f64>,
+    /// `back`, `front` or `none`
+    cull: Option<CullMode>,
+}
+
+fn default_camera_position() -> [f64; 3] {
+    [0., 0., -3.]
+}
+
+fn default_fov() -> f64 {
+    Camera::default().fov
+}
+
+fn default_size() -> [f64; 3] {
+    [1., 1., 1.]
+}
+
+fn default_light_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+fn default_range() -> f64 {
+    10.
+}
+
+fn default_shadows() -> bool {
+    true
+}
+
+fn to_vector3(v: [f64; 3]) -> Vector3 {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+fn to_color([r, g, b]: [u8; 3]) -> RgbColor {
+    RgbColor::new(r, g, b)
+}
+
+impl From<&LightDescription> for Light {
+    fn from(description: &LightDescription) -> Self {
+        match *description {
+            LightDescription::Ambient { color, intensity } => Light::Ambient {
+                color: to_color(color),
+                intensity,
+            },
+            LightDescription::Directional {
+                direction,
+                color,
+                intensity,
+                shadows,
+            } => Light::Directional {
+                direction: to_vector3(direction),
+                color: to_color(color),
+                intensity,
+                shadows,
+            },
+            LightDescription::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => Light::Point {
+                position: to_vector3(position),
+                color: to_color(color),
+                intensity,
+                range,
+            },
+        }
+    }
+}
+
+/// Loads a scene and its camera, if it has one
+pub fn load(path: &Path) -> Result<(Scene, Option<Camera>), LoadError> {
+    let source = fs::read_to_string(path)?;
+    let description: SceneDescription =
+        serde_json::from_str(&source).map_err(|error| LoadError::Parse {
+            line: 0,
+            message: error.to_string(),
+        })?;
+
+    let mut loader = Loader {
+        directory: path.parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+        models: HashMap::new(),
+        textures: HashMap::new(),
+    };
+    let nodes = description
+        .nodes
+        .iter()
+        .map(|node| loader.load_node(node))
+        .collect::<Result<Vec<Node>, LoadError>>()?;
+
+    let camera = description.camera.map(|camera| Camera {
+        position: to_vector3(camera.position),
+        euler_angles: to_vector3(camera.euler_angles),
+        fov: camera.fov,
+        ..Camera::default()
+    });
+
+    let lights = match description.lights {
+        Some(lights) => lights.iter().map(Light::from).collect(),
+        None => Scene::default().lights,
+    };
+
+    Ok((Scene { nodes, lights }, camera))
+}
+
+struct Loader {
+    directory: PathBuf,
+    /// Nodes without material overrides share their models, glTF files are node hierarchies
+    models: HashMap<PathBuf, Node>,
+    textures: HashMap<PathBuf, Rc<Texture>>,
+}
+
+impl Loader {
+    fn load_node(&mut self, description: &NodeDescription) -> Result<Node, LoadError> {
+        // The model of the node and, for glTF files, its node hierarchy
+        let mut loaded = match (&description.model, description.primitive) {
+            (Some(_), Some(_)) => {
+                return Err(LoadError::Parse {
+                    line: 0,
+                    message: format!(
+                        "node '{}' has both a model and a primitive",
+                        description.name
+                    ),
+                })
+            }
+            (Some(path), None) => self.load_model(path)?,
+            (None, Some(primitive)) => Node::new("", Some(Rc::new(Model::from(primitive)))),
+            (None, None) => Node::default(),
+        };
+        if let Some(material) = &description.material {
+            self.apply_material(&mut loaded, material)?;
+        }
+
+        let mut children = loaded.children;
+        for child in description.children.iter() {
+            children.push(self.load_node(child)?);
+        }
+        Ok(Node {
+            name: description.name.clone(),
+            pos: to_vector3(description.pos),
+            rotation: Quaternion::from_euler(&to_vector3(description.euler_angles)),
+            size: to_vector3(description.size),
+            spin: to_vector3(description.spin),
+            renderable: loaded.renderable,
+            children,
+        })
+    }
+
+    fn load_model(&mut self, path: &Path) -> Result<Node, LoadError> {
+        let path = self.directory.join(path);
+        if let Some(node) = self.models.get(&path) {
+            return Ok(node.clone());
+        }
+
+        let node =
+            Node::load(&path).map_err(|error| LoadError::InFile(path.clone(), Box::new(error)))?;
+        self.models.insert(path, node.clone());
+        Ok(node)
+    }
+
+    /// To every model of the node and its children
+    fn apply_material(
+        &mut self,
+        node: &mut Node,
+        description: &MaterialDescription,
+    ) -> Result<(), LoadError> {
+        let texture = match &description.texture {
+            Some(path) => Some(self.load_texture(path)?),
+            None => None,
+        };
+
+        node.for_each_model_mut(&mut |model| {
+            for material in model.materials.iter_mut() {
+                if let Some(color) = description.color {
+                    material.base_color = to_color(color);
+                    material.texture = None;
+                }
+                if texture.is_some() {
+                    material.texture = texture.clone();
+                }
+                if let Some(shading) = description.shading {
+                    material.shading = shading;
+                }
+                if let Some(specular) = description.specular {
+                    material.specular = to_color(specular);
+                }
+                if let Some(shininess) = description.shininess {
+                    material.shininess = shininess;
+                }
+                if let Some(cull) = description.cull {
+                    material.cull_mode = cull;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn load_texture(&mut self, path: &Path) -> Result<Rc<Texture>, LoadError> {
+        let path = self.directory.join(path);
+        if let Some(texture) = self.textures.get(&path) {
+            return Ok(texture.clone());
+        }
+
+        let image = image::open(&path)
+            .map_err(|error| LoadError::InFile(path.clone(), Box::new(error.into())))?;
+        let texture = Rc::new(Texture::new(image));
+        self.textures.insert(path, texture.clone());
+        Ok(texture)
+    }
+}
+
+impl From<Primitive> for Model {
+    fn from(primitive: Primitive) -> Self {
+        let mesh = match primitive {
+            Primitive::Cube => return Model::from(&crate::cube::Cube::unit()),
+            Primitive::Plane => Mesh::plane(),
+            Primitive::UvSphere => Mesh::uv_sphere(24, 16),
+            Primitive::Icosphere => Mesh::icosphere(2),
+            Primitive::Cylinder => Mesh::cylinder(24),
+            Primitive::Cone => Mesh::cone(24),
+            Primitive::Torus => Mesh::torus(0.7, 0.3, 32, 16),
+        };
+        Model::new(
+            mesh,
+            vec![Material {
+                shading: Shading::Gouraud,
+                ..Material::default()
+            }],
+        )
+    }
+}
+
+/// Notices when a file changes on disk, by polling its modification time
+pub struct FileWatcher {
+    pub path: PathBuf,
+    /// `None` before the first poll, `Some(None)` while the file is missing
+    last_modified: Option<Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> FileWatcher {
+        FileWatcher {
+            path: path.to_path_buf(),
+            last_modified: None,
+        }
+    }
+
+    /// `true` on the first call and whenever the file was modified, removed or recreated since the last call
+    pub fn poll(&mut self) -> bool {
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if self.last_modified == Some(modified) {
+            return false;
+        }
+        self.last_modified = Some(modified);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+    use image::{DynamicImage, RgbImage};
+    use std::fs::File;
+    use std::time::Duration;
+
+    const TRIANGLE_OBJ: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+    fn load_error(directory: &Path, source: &str) -> String {
+        let path = directory.join("scene.json");
+        fs::write(&path, source).unwrap();
+        match load(&path) {
+            Ok(_) => panic!("{} loaded", source),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn loads_the_documented_example() {
+        let directory = TestDirectory::new("scene-example");
+        DynamicImage::ImageRgb8(RgbImage::new(2, 2))
+            .save(directory.join("cat.png"))
+            .unwrap();
+        fs::write(directory.join("planet.obj"), TRIANGLE_OBJ).unwrap();
+        let path = directory.join("scene.json");
+        fs::write(
+            &path,
+            r#"{
+                "camera": { "position": [0, 0, -3], "fov": 40 },
+                "lights": [
+                    { "type": "ambient", "intensity": 0.2 },
+                    { "type": "point", "position": [0, -2, -2], "color": [255, 200, 150], "range": 8 }
+                ],
+                "nodes": [
+                    {
+                        "name": "sun",
+                        "primitive": "cube",
+                        "size": [0.5, 0.5, 0.5],
+                        "spin": [0, 30, 0],
+                        "material": { "texture": "cat.png" },
+                        "children": [{ "model": "planet.obj", "pos": [3, 0, 0] }]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let (scene, camera) = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        let camera = camera.unwrap();
+        assert_eq!(camera.position.z, -3.);
+        assert_eq!(camera.fov, 40.);
+        assert_eq!(scene.lights.len(), 2);
+        assert!(matches!(
+            scene.lights[1],
+            Light::Point { range, .. } if range == 8.
+        ));
+
+        let sun = scene.find("sun").unwrap();
+        assert_eq!(sun.size.x, 0.5);
+        assert_eq!(sun.spin.y, 30.);
+        let model = sun.renderable.as_ref().unwrap();
+        assert!(model.materials.iter().all(|m| m.texture.is_some()));
+        let planet = &sun.children[0];
+        assert_eq!(planet.pos.x, 3.);
+        assert_eq!(
+            planet.renderable.as_ref().unwrap().mesh().triangle_count(),
+            1
+        );
+    }
+
+    #[test]
+    fn defaults_without_camera_and_lights() {
+        let directory = TestDirectory::new("scene-defaults");
+        let path = directory.join("scene.json");
+        fs::write(&path, "{}").unwrap();
+
+        let (scene, camera) = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        assert!(camera.is_none());
+        assert!(scene.nodes.is_empty());
+        assert_eq!(scene.lights.len(), Scene::default().lights.len());
+    }
+
+    #[test]
+    fn unknown_fields_are_errors() {
+        let directory = TestDirectory::new("scene-unknown");
+        for (source, field) in [
+            (r#"{ "node": [] }"#, "node"),
+            (r#"{ "camera": { "pos": [0, 0, 0] } }"#, "pos"),
+            (r#"{ "nodes": [{ "position": [0, 0, 0] }] }"#, "position"),
+            (
+                r#"{ "lights": [{ "type": "ambient", "range": 2 }] }"#,
+                "range",
+            ),
+            (
+                r#"{ "nodes": [{ "material": { "colour": [0, 0, 0] } }] }"#,
+                "colour",
+            ),
+        ] {
+            let error = load_error(&directory, source);
+            assert!(error.contains(field), "{}", error);
+        }
+    }
+
+    #[test]
+    fn model_and_primitive_are_an_error() {
+        let directory = TestDirectory::new("scene-both");
+        fs::write(directory.join("a.obj"), TRIANGLE_OBJ).unwrap();
+        let error = load_error(
+            &directory,
+            r#"{ "nodes": [{ "name": "both", "model": "a.obj", "primitive": "cube" }] }"#,
+        );
+        assert!(error.contains("'both'"), "{}", error);
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_scene_file() {
+        let directory = TestDirectory::new("scene-paths");
+        fs::create_dir_all(directory.join("scenes")).unwrap();
+        fs::create_dir_all(directory.join("models")).unwrap();
+        fs::write(directory.join("models").join("a.obj"), TRIANGLE_OBJ).unwrap();
+        let path = directory.join("scenes").join("scene.json");
+        fs::write(
+            &path,
+            r#"{ "nodes": [{ "model": "../models/a.obj" }, { "model": "../models/a.obj" }] }"#,
+        )
+        .unwrap();
+
+        let (scene, _) = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        let models: Vec<&Rc<Model>> = scene
+            .nodes
+            .iter()
+            .map(|node| node.renderable.as_ref().unwrap())
+            .collect();
+        // Loaded once and shared
+        assert!(Rc::ptr_eq(models[0], models[1]));
+
+        // Errors name the resolved path
+        fs::write(&path, r#"{ "nodes": [{ "model": "a.obj" }] }"#).unwrap();
+        let error = load(&path).err().unwrap().to_string();
+        assert!(
+            error.contains(&directory.join("scenes").join("a.obj").display().to_string()),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn watcher_notices_changes() {
+        let directory = TestDirectory::new("scene-watcher");
+        let path = directory.join("scene.json");
+        fs::write(&path, "{}").unwrap();
+        let mut watcher = FileWatcher::new(&path);
+
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // The modification time can be too coarse to notice a write right away
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::write(&path, "{}").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn watcher_fires_once_for_a_missing_file() {
+        let directory = TestDirectory::new("scene-missing");
+        let mut watcher = FileWatcher::new(&directory.join("scene.json"));
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+}
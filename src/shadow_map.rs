@@ -0,0 +1,95 @@
+use crate::matrix4::Matrix4;
+use crate::rasterizer::{self, Fragment};
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+
+/// Texels per side
+pub const SHADOW_MAP_SIZE: u16 = 256;
+
+/// Keeps surfaces from shadowing themselves, in the `0` to `1` depth range
+const DEPTH_BIAS: f64 = 0.002;
+
+/// Depth of everything as seen from a directional light
+pub struct ShadowMap {
+    /// World space to the light's normalized device coordinates, orthographic so there is no perspective divide
+    view_projection: Matrix4,
+    size: u16,
+    depth: Vec<f64>,
+    /// Width of a texel in world space
+    texel_size: f64,
+}
+
+impl ShadowMap {
+    /// Covers a sphere around `center`, for a light that travels towards `direction`
+    pub fn new(direction: &Vector3, center: &Vector3, radius: f64, size: u16) -> ShadowMap {
+        let direction = direction.normalized();
+        let eye = *center - direction * (2. * radius);
+        // Any up vector works, as long as it isn't parallel to the light
+        let up = if direction.y.abs() > 0.99 {
+            Vector3::new(0., 0., 1.)
+        } else {
+            Vector3::new(0., -1., 0.)
+        };
+        let view = Matrix4::look_at(&eye, center, &up);
+        let projection = Matrix4::orthographic(radius, radius, radius, 3. * radius);
+
+        ShadowMap {
+            view_projection: projection * view,
+            size,
+            depth: vec![f64::INFINITY; size as usize * size as usize],
+            texel_size: 2. * radius / size as f64,
+        }
+    }
+
+    /// Light space `x` and `y` in texels, and the depth
+    fn project(&self, position: &Vector3) -> Vector3 {
+        let projected = self.view_projection.transform_point(position);
+        let half_size = 0.5 * self.size as f64;
+        Vector3::new(
+            projected.x * half_size + half_size,
+            projected.y * half_size + half_size,
+            projected.z,
+        )
+    }
+
+    /// Adds a triangle in world space, both sides cast shadows
+    pub fn render_triangle(&mut self, vertices: &[Vector3; 3]) {
+        let projected = vertices.map(|v| self.project(&v));
+        let screen = projected.map(|v| Vector2::new(v.x, v.y));
+
+        let size = self.size;
+        rasterizer::rasterize_triangle(&screen, size, size, |fragment: Fragment| {
+            let b = &fragment.barycentric;
+            // Orthographic, so the depth is linear
+            let depth = projected[0].z * b[0] + projected[1].z * b[1] + projected[2].z * b[2];
+            let index = fragment.row as usize * size as usize + fragment.column as usize;
+            if depth < self.depth[index] {
+                self.depth[index] = depth;
+            }
+        });
+    }
+
+    /// `1` if the light reaches `position`, `0` if it is in shadow and in between at the edges of shadows.
+    ///
+    /// Averages the 3x3 texels around the position (percentage-closer filtering).
+    pub fn visibility(&self, position: &Vector3, normal: &Vector3) -> f64 {
+        // Moving the position off the surface keeps slopes from shadowing themselves
+        let projected = self.project(&(*position + *normal * (1.5 * self.texel_size)));
+        let depth = projected.z - DEPTH_BIAS;
+        let column = projected.x.floor() as i64;
+        let row = projected.y.floor() as i64;
+
+        let mut lit = 0;
+        for y in row - 1..=row + 1 {
+            for x in column - 1..=column + 1 {
+                // Nothing casts shadows outside of the map
+                let inside = x >= 0 && y >= 0 && x < self.size as i64 && y < self.size as i64;
+                if !inside || depth <= self.depth[y as usize * self.size as usize + x as usize] {
+                    lit += 1;
+                }
+            }
+        }
+
+        lit as f64 / 9.
+    }
+}
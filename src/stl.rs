@@ -0,0 +1,255 @@
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::material::{Material, Shading};
+use crate::mesh::Mesh;
+use crate::model::{LoadError, Model};
+use crate::vector3::Vector3;
+
+/// Header, triangle count and then 50 bytes per triangle
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// Loads an ASCII or binary STL file, centered and scaled to fit into `-1` to `1`.
+///
+/// STL files are usually Z-up, so Z becomes -Y.
+/// Files without any triangles are an error, like PLY files without faces.
+pub fn load(path: &Path) -> Result<Model, LoadError> {
+    let bytes = fs::read(path)?;
+
+    // Binary files can also start with "solid", but their size is always known
+    let is_binary = bytes.len() >= BINARY_HEADER_SIZE && {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        bytes.len() == BINARY_HEADER_SIZE + count * BINARY_TRIANGLE_SIZE
+    };
+    let triangles = if is_binary {
+        parse_binary(&bytes)
+    } else if bytes.starts_with(b"solid") {
+        parse_ascii(&String::from_utf8_lossy(&bytes))?
+    } else {
+        return Err(LoadError::Parse {
+            line: 0,
+            message: "not an STL file".to_string(),
+        });
+    };
+    if triangles.is_empty() {
+        return Err(LoadError::Parse {
+            line: 0,
+            message: "the file has no faces".to_string(),
+        });
+    }
+
+    let mut mesh = Mesh::new(Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (normal, corners) in triangles {
+        // The stored normal is often left at zero
+        let normal = if normal.length() > 0. {
+            normal.normalized()
+        } else {
+            (corners[1] - corners[0])
+                .cross(&(corners[2] - corners[0]))
+                .normalized()
+        };
+        let start = mesh.positions.len() as u32;
+        mesh.positions
+            .extend(corners.iter().map(|&corner| from_z_up(corner)));
+        mesh.normals.extend_from_slice(&[from_z_up(normal); 3]);
+        mesh.indices
+            .extend_from_slice(&[start, start + 1, start + 2]);
+    }
+    mesh.groups[0].count = mesh.indices.len();
+    mesh.center_and_scale();
+
+    Ok(Model::new(
+        mesh,
+        vec![Material {
+            shading: Shading::Flat,
+            ..Material::default()
+        }],
+    ))
+}
+
+/// A rotation, so the winding order stays the same
+fn from_z_up(v: Vector3) -> Vector3 {
+    Vector3::new(v.x, -v.z, v.y)
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<(Vector3, [Vector3; 3])> {
+    let read_vector = |offset: usize| {
+        let read_f32 = |offset: usize| {
+            f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64
+        };
+        Vector3::new(read_f32(offset), read_f32(offset + 4), read_f32(offset + 8))
+    };
+
+    (BINARY_HEADER_SIZE..bytes.len())
+        .step_by(BINARY_TRIANGLE_SIZE)
+        .map(|offset| {
+            (
+                read_vector(offset),
+                [
+                    read_vector(offset + 12),
+                    read_vector(offset + 24),
+                    read_vector(offset + 36),
+                ],
+            )
+        })
+        .collect()
+}
+
+fn parse_ascii(source: &str) -> Result<Vec<(Vector3, [Vector3; 3])>, LoadError> {
+    let mut triangles = Vec::new();
+    let mut normal = Vector3::zero();
+    let mut corners = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: String| LoadError::Parse {
+            line: index + 1,
+            message,
+        };
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            // "facet normal x y z"
+            Some(&"facet") => {
+                normal = parse_vector(tokens.get(2..)).map_err(error)?;
+                corners.clear();
+            }
+            // "vertex x y z"
+            Some(&"vertex") => corners.push(parse_vector(tokens.get(1..)).map_err(error)?),
+            Some(&"endfacet") => {
+                if corners.len() != 3 {
+                    return Err(error("a facet needs exactly three vertices".to_string()));
+                }
+                triangles.push((normal, [corners[0], corners[1], corners[2]]));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn parse_vector(tokens: Option<&[&str]>) -> Result<Vector3, String> {
+    let tokens = match tokens {
+        Some(tokens) if tokens.len() >= 3 => tokens,
+        _ => return Err("expected three numbers".to_string()),
+    };
+    let parse = |token: &str| {
+        token
+            .parse::<f64>()
+            .map_err(|_| format!("'{}' is not a number", token))
+    };
+    Ok(Vector3::new(
+        parse(tokens[0])?,
+        parse(tokens[1])?,
+        parse(tokens[2])?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    fn write_binary(path: &Path, header: &[u8; 80], count: u32, triangles: &[[f32; 12]]) {
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(&count.to_le_bytes());
+        for triangle in triangles {
+            for value in triangle {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            // Attribute byte count
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn positions(model: &Model) -> Vec<(f64, f64, f64)> {
+        model
+            .mesh()
+            .positions
+            .iter()
+            .map(|p| (p.x, p.y, p.z))
+            .collect()
+    }
+
+    #[test]
+    fn ascii_is_turned_z_up_centered_and_scaled() {
+        let directory = TestDirectory::new("stl-ascii");
+        let path = directory.join("model.stl");
+        fs::write(
+            &path,
+            "solid test\n\
+             facet normal 0 0 0\nouter loop\n\
+             vertex 0 0 0\nvertex 4 0 0\nvertex 0 0 4\n\
+             endloop\nendfacet\nendsolid test\n",
+        )
+        .unwrap();
+
+        let model = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            positions(&model),
+            [(-1., 1., 0.), (1., 1., 0.), (-1., -1., 0.)]
+        );
+        // Computed from the corners, facing the viewer
+        let normal = model.mesh().normals[0];
+        assert_eq!((normal.x, normal.y, normal.z), (0., 0., -1.));
+    }
+
+    #[test]
+    fn binary_is_detected_by_its_size() {
+        let directory = TestDirectory::new("stl-binary");
+        let path = directory.join("model.stl");
+        // Starts with "solid" like an ASCII file
+        let mut header = [b' '; 80];
+        header[..5].copy_from_slice(b"solid");
+        write_binary(
+            &path,
+            &header,
+            1,
+            &[[0., 0., 1., 0., 0., 0., 2., 0., 0., 0., 0., 2.]],
+        );
+
+        let model = load(&path).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            positions(&model),
+            [(-1., 1., 0.), (1., 1., 0.), (-1., -1., 0.)]
+        );
+        // The stored normal, up becomes -Y
+        let normal = model.mesh().normals[0];
+        assert_eq!((normal.x, normal.y, normal.z), (0., -1., 0.));
+    }
+
+    #[test]
+    fn empty_files_are_errors() {
+        let directory = TestDirectory::new("stl-empty");
+        let path = directory.join("model.stl");
+
+        fs::write(&path, "solid empty\nendsolid empty\n").unwrap();
+        assert!(load(&path).is_err());
+
+        write_binary(&path, &[0; 80], 0, &[]);
+        assert!(load(&path).is_err());
+
+        fs::write(&path, "").unwrap();
+        assert!(load(&path).is_err());
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let directory = TestDirectory::new("stl-truncated");
+        let path = directory.join("model.stl");
+
+        // Two triangles announced, one written
+        write_binary(&path, &[0; 80], 2, &[[0.; 12]]);
+        assert!(load(&path).is_err());
+
+        fs::write(
+            &path,
+            "solid test\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\n",
+        )
+        .unwrap();
+        assert!(load(&path).is_err());
+    }
+}
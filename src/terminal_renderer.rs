@@ -1,19 +1,240 @@
 use crossterm::{
     cursor::{Hide, MoveTo},
-    event, execute, queue,
-    style::{
-        self, Color, ContentStyle, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
-    },
+    queue,
+    style::{self, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
     terminal, Result,
 };
+use image::RgbImage;
 use std::io::{stdout, Write};
 
+use crate::camera::Camera;
+
+#[derive(Copy, Clone)]
 pub struct RgbColor {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl RgbColor {
+    pub const BLACK: RgbColor = RgbColor { r: 0, g: 0, b: 0 };
+    pub const WHITE: RgbColor = RgbColor {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    pub fn new(r: u8, g: u8, b: u8) -> RgbColor {
+        RgbColor { r, g, b }
+    }
+
+    /// From the `0` to `1` range, values outside of it are clamped
+    pub fn from_f64(r: f64, g: f64, b: f64) -> RgbColor {
+        let convert = |v: f64| (v.clamp(0., 1.) * 255.).round() as u8;
+        RgbColor::new(convert(r), convert(g), convert(b))
+    }
+
+    /// Component-wise, as if both colors were in the `0` to `1` range
+    pub fn multiply(&self, rhs: &RgbColor) -> RgbColor {
+        let multiply = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+        RgbColor {
+            r: multiply(self.r, rhs.r),
+            g: multiply(self.g, rhs.g),
+            b: multiply(self.b, rhs.b),
+        }
+    }
+
+    /// `self` at `0` and `rhs` at `1`
+    pub fn lerp(&self, rhs: &RgbColor, t: f64) -> RgbColor {
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        RgbColor {
+            r: lerp(self.r, rhs.r),
+            g: lerp(self.g, rhs.g),
+            b: lerp(self.b, rhs.b),
+        }
+    }
+
+    /// Squared Euclidean distance between the colors
+    fn distance_squared(&self, rhs: &RgbColor) -> i32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(self.r, rhs.r) + d(self.g, rhs.g) + d(self.b, rhs.b)
+    }
+
+    /// Average of the colors, black if there are none
+    fn average(colors: &[RgbColor]) -> RgbColor {
+        let count = colors.len().max(1) as u32;
+        let sum = |get: fn(&RgbColor) -> u8| {
+            (colors.iter().map(|c| get(c) as u32).sum::<u32>() + count / 2) / count
+        };
+        RgbColor::new(sum(|c| c.r) as u8, sum(|c| c.g) as u8, sum(|c| c.b) as u8)
+    }
+
+    /// Component-wise, saturating at white
+    pub fn add(&self, rhs: &RgbColor) -> RgbColor {
+        RgbColor {
+            r: self.r.saturating_add(rhs.r),
+            g: self.g.saturating_add(rhs.g),
+            b: self.b.saturating_add(rhs.b),
+        }
+    }
+}
+
+/// How many colors the terminal supports
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24-bit colors
+    TrueColor,
+    /// The 6x6x6 color cube and the grayscale ramp of 256 color terminals
+    Ansi256,
+    /// The basic colors
+    Ansi16,
+}
+
+/// How the pixels of a terminal cell are drawn
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum CellMode {
+    /// Lower half block `▄`, the top pixel is the background and the bottom one the foreground
+    HalfBlock,
+    /// Bold underlined `_` in place of the half block, for terminals and fonts without it (like cmd)
+    Underscore,
+    /// 2x2 pixels with the quadrant block characters, like `▚`
+    Quadrant,
+    /// 2x4 pixels with the Braille patterns, like `⢕`
+    Braille,
+}
+
+/// Width divided by height of a terminal cell, most fonts are about twice as high as they are wide
+pub const CELL_ASPECT_RATIO: f64 = 0.5;
+
+impl CellMode {
+    /// `(columns, rows)` of pixels in a terminal cell
+    pub fn pixels(&self) -> (u16, u16) {
+        match self {
+            CellMode::HalfBlock | CellMode::Underscore => (1, 2),
+            CellMode::Quadrant => (2, 2),
+            CellMode::Braille => (2, 4),
+        }
+    }
+
+    /// `(width, height)` in pixels of `columns` by `rows` cells, `None` if it doesn't fit in a `u16`
+    pub fn pixel_size(&self, columns: u16, rows: u16) -> Option<(u16, u16)> {
+        let (cell_width, cell_height) = self.pixels();
+        Some((
+            columns.checked_mul(cell_width)?,
+            rows.checked_mul(cell_height)?,
+        ))
+    }
+
+    /// Width divided by height of a pixel
+    pub fn pixel_aspect_ratio(&self) -> f64 {
+        let (columns, rows) = self.pixels();
+        CELL_ASPECT_RATIO * rows as f64 / columns as f64
+    }
+
+    /// Character with the foreground in the pixels of `mask`, row by row.
+    /// Half blocks and underscores always cover the bottom pixel and ignore the mask.
+    fn character(&self, mask: u8) -> char {
+        match self {
+            CellMode::HalfBlock => '▄',
+            CellMode::Underscore => '_',
+            CellMode::Quadrant => QUADRANTS[(mask & 0xf) as usize],
+            CellMode::Braille => {
+                let dots = (0..BRAILLE_DOTS.len())
+                    .filter(|i| mask & (1 << i) != 0)
+                    .map(|i| BRAILLE_DOTS[i])
+                    .sum::<u32>();
+                char::from_u32(0x2800 + dots).unwrap_or(' ')
+            }
+        }
+    }
+}
+
+/// Quadrant blocks for the pixels that are set, bit `0` is the top left, `1` the top right,
+/// `2` the bottom left and `3` the bottom right
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Bit of the Braille dot for each pixel of the 2x4 cell, row by row
+const BRAILLE_DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+impl RgbColor {
+    /// Closest color that the terminal can show
+    pub fn to_color(&self, color_depth: ColorDepth) -> Color {
+        match color_depth {
+            ColorDepth::TrueColor => Color::from(*self),
+            ColorDepth::Ansi256 => Color::AnsiValue(self.to_ansi256()),
+            ColorDepth::Ansi16 => self.to_ansi16(),
+        }
+    }
+
+    fn to_ansi256(self) -> u8 {
+        // Levels of the color cube are 0, 95, 135, 175, 215 and 255
+        let to_level = |v: u8| {
+            if v < 48 {
+                0
+            } else if v < 115 {
+                1
+            } else {
+                (v - 35) / 40
+            }
+        };
+        let level_value = |level: u8| if level == 0 { 0 } else { 55 + level * 40 };
+        let (r, g, b) = (to_level(self.r), to_level(self.g), to_level(self.b));
+        let cube = (
+            16 + 36 * r + 6 * g + b,
+            [level_value(r), level_value(g), level_value(b)],
+        );
+
+        // 24 shades of gray from 8 to 238
+        let average = (self.r as u16 + self.g as u16 + self.b as u16) / 3;
+        let gray_index = ((average.saturating_sub(3)) / 10).min(23) as u8;
+        let gray_value = 8 + gray_index * 10;
+        let gray = (232 + gray_index, [gray_value; 3]);
+
+        let distance = |[r, g, b]: [u8; 3]| {
+            let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            d(r, self.r) + d(g, self.g) + d(b, self.b)
+        };
+        if distance(gray.1) < distance(cube.1) {
+            gray.0
+        } else {
+            cube.0
+        }
+    }
+
+    fn to_ansi16(self) -> Color {
+        const COLORS: [(Color, [u8; 3]); 16] = [
+            (Color::Black, [0, 0, 0]),
+            (Color::DarkRed, [128, 0, 0]),
+            (Color::DarkGreen, [0, 128, 0]),
+            (Color::DarkYellow, [128, 128, 0]),
+            (Color::DarkBlue, [0, 0, 128]),
+            (Color::DarkMagenta, [128, 0, 128]),
+            (Color::DarkCyan, [0, 128, 128]),
+            (Color::Grey, [192, 192, 192]),
+            (Color::DarkGrey, [128, 128, 128]),
+            (Color::Red, [255, 0, 0]),
+            (Color::Green, [0, 255, 0]),
+            (Color::Yellow, [255, 255, 0]),
+            (Color::Blue, [0, 0, 255]),
+            (Color::Magenta, [255, 0, 255]),
+            (Color::Cyan, [0, 255, 255]),
+            (Color::White, [255, 255, 255]),
+        ];
+
+        let distance = |[r, g, b]: [u8; 3]| {
+            let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            d(r, self.r) + d(g, self.g) + d(b, self.b)
+        };
+        COLORS
+            .iter()
+            .min_by_key(|(_, rgb)| distance(*rgb))
+            .map(|(color, _)| *color)
+            .unwrap()
+    }
+}
+
 impl From<RgbColor> for crossterm::style::Color {
     fn from(item: RgbColor) -> Self {
         Color::Rgb {
@@ -24,33 +245,62 @@ impl From<RgbColor> for crossterm::style::Color {
     }
 }
 
-const HEIGHT_SCALE: u16 = 2; // TODO: This is totally a hack;
-
 pub struct DrawBuffer {
     pub buffer: Vec<u8>,
+    /// One depth value per pixel, smaller is closer
+    pub depth: Vec<f64>,
+    /// In pixels
     pub width: u16,
     pub height: u16,
+    /// Decides how many pixels there are per terminal cell, changes apply with the next `update_size`
+    pub cell_mode: CellMode,
+}
+
+impl Default for DrawBuffer {
+    fn default() -> Self {
+        DrawBuffer::new(CellMode::HalfBlock)
+    }
 }
 
 impl DrawBuffer {
-    pub fn new() -> DrawBuffer {
-        let terminal_size = get_terminal_size();
-        let buffer_size = DrawBuffer::get_buffer_size(terminal_size);
-        let mut buffer = Vec::with_capacity(buffer_size);
-        buffer.resize(buffer_size, 0);
+    /// As large as the terminal
+    pub fn new(cell_mode: CellMode) -> DrawBuffer {
+        let terminal_size = get_terminal_size(cell_mode);
         DrawBuffer {
-            buffer,
+            buffer: vec![0; DrawBuffer::get_buffer_size(terminal_size)],
+            depth: vec![f64::INFINITY; DrawBuffer::get_depth_size(terminal_size)],
             width: terminal_size.0,
             height: terminal_size.1,
+            cell_mode,
         }
     }
 
+    /// Fixed size in terminal cells, for rendering without a terminal.
+    /// `None` if the size in pixels is too large.
+    pub fn with_size(columns: u16, rows: u16, cell_mode: CellMode) -> Option<DrawBuffer> {
+        let (width, height) = cell_mode.pixel_size(columns, rows)?;
+        Some(DrawBuffer {
+            buffer: vec![0; DrawBuffer::get_buffer_size((width, height))],
+            depth: vec![f64::INFINITY; DrawBuffer::get_depth_size((width, height))],
+            width,
+            height,
+            cell_mode,
+        })
+    }
+
+    /// Width divided by height of the whole buffer, as it appears in the terminal
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width.max(1) as f64 * self.cell_mode.pixel_aspect_ratio() / self.height.max(1) as f64
+    }
+
     pub fn update_size(self: &mut DrawBuffer) {
-        let terminal_size = get_terminal_size();
+        let terminal_size = get_terminal_size(self.cell_mode);
 
         if self.width != terminal_size.0 || self.height != terminal_size.1 {
             self.buffer
                 .resize(DrawBuffer::get_buffer_size(terminal_size), 0);
+            self.depth
+                .resize(DrawBuffer::get_depth_size(terminal_size), f64::INFINITY);
             self.width = terminal_size.0;
             self.height = terminal_size.1;
         }
@@ -58,6 +308,7 @@ impl DrawBuffer {
 
     pub fn clear(self: &mut DrawBuffer) {
         self.buffer.fill(0);
+        self.depth.fill(f64::INFINITY);
     }
 
     pub fn get_color(&self, column: u16, row: u16) -> RgbColor {
@@ -74,7 +325,60 @@ impl DrawBuffer {
     }
 
     fn get_buffer_size(terminal_size: (u16, u16)) -> usize {
-        (terminal_size.0 as usize * terminal_size.1 as usize * 3).into()
+        DrawBuffer::get_depth_size(terminal_size) * 3
+    }
+
+    fn get_depth_size(terminal_size: (u16, u16)) -> usize {
+        terminal_size.0 as usize * terminal_size.1 as usize
+    }
+
+    /// Returns `true` and stores the new depth if nothing closer has been drawn there yet
+    pub fn depth_test(&mut self, column: u16, row: u16, depth: f64) -> bool {
+        if column >= self.width || row >= self.height {
+            return false;
+        }
+
+        let pos: usize = row as usize * self.width as usize + column as usize;
+        if depth > self.depth[pos] {
+            return false;
+        }
+
+        self.depth[pos] = depth;
+        true
+    }
+
+    /// Replaces the colors with the distance to the camera, the closest pixel is white and the farthest one is dark gray
+    pub fn show_depth(&mut self, camera: &Camera) {
+        let (near, far) = (camera.near, camera.far);
+        // Undoes the perspective projection
+        let linear: Vec<f64> = self
+            .depth
+            .iter()
+            .map(|&depth| far * near / (far - depth * (far - near)))
+            .collect();
+
+        let visible = || {
+            linear
+                .iter()
+                .filter(|depth| depth.is_finite() && **depth > 0.)
+        };
+        let closest = visible().fold(f64::INFINITY, |a, &b| a.min(b));
+        let farthest = visible().fold(0., |a: f64, &b| a.max(b));
+        let range = (farthest - closest).max(f64::EPSILON);
+
+        for (index, depth) in linear.iter().enumerate() {
+            let brightness = if self.depth[index].is_finite() {
+                1. - 0.8 * (depth - closest) / range
+            } else {
+                0.
+            };
+            let color = RgbColor::from_f64(brightness, brightness, brightness);
+            self.buffer[index * 3..index * 3 + 3].copy_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_raw(self.width as u32, self.height as u32, self.buffer.clone()).unwrap()
     }
 
     pub fn set_color(&mut self, column: u16, row: u16, color: &RgbColor) {
@@ -88,41 +392,300 @@ impl DrawBuffer {
         self.buffer[pos + 2] = color.b;
     }
 }
-/// `(columns, rows)`
-fn get_terminal_size() -> (u16, u16) {
+/// `(width, height)` in pixels
+fn get_terminal_size(cell_mode: CellMode) -> (u16, u16) {
     let size = terminal::size().unwrap_or((1, 1));
-    (size.0, size.1 * HEIGHT_SCALE)
+    let (cell_width, cell_height) = cell_mode.pixels();
+    (
+        size.0.saturating_mul(cell_width),
+        size.1.saturating_mul(cell_height),
+    )
 }
 
-pub fn render(buffer: &DrawBuffer) -> Result<()> {
+pub fn render(buffer: &DrawBuffer, color_depth: ColorDepth) -> Result<()> {
     let mut stdout = stdout();
-    let terminal_size = get_terminal_size();
+    queue_frame(&mut stdout, buffer, color_depth)?;
+    stdout.flush()?;
+    Ok(())
+}
 
-    queue!(stdout, Hide)?;
-    if HEIGHT_SCALE >= 2 {
-        queue!(stdout, SetAttribute(style::Attribute::Bold))?;
-        queue!(stdout, SetAttribute(style::Attribute::Underlined))?;
+/// Every line of terminal cells at its place on the screen
+fn queue_frame<W: Write>(out: &mut W, buffer: &DrawBuffer, color_depth: ColorDepth) -> Result<()> {
+    let cell_height = buffer.cell_mode.pixels().1;
+
+    queue!(out, Hide)?;
+    for row in (0..buffer.height).step_by(cell_height.into()) {
+        queue!(out, MoveTo(0, row / cell_height))?;
+        queue_row(out, buffer, row, color_depth)?;
     }
+    Ok(())
+}
 
-    for row in (0..terminal_size.1).step_by(HEIGHT_SCALE.into()) {
-        queue!(stdout, MoveTo(0, row / 2))?;
+/// The whole buffer as lines of text with ANSI escape codes, for example to save it to a file
+pub fn write_ansi<W: Write>(
+    out: &mut W,
+    buffer: &DrawBuffer,
+    color_depth: ColorDepth,
+) -> Result<()> {
+    let cell_height = buffer.cell_mode.pixels().1;
+    for row in (0..buffer.height).step_by(cell_height.into()) {
+        queue_row(out, buffer, row, color_depth)?;
+        queue!(out, Print("\n"))?;
+    }
 
-        for column in 0..terminal_size.0 {
-            let color = buffer.get_color(column, row);
+    out.flush()?;
+    Ok(())
+}
 
-            // TODO: Only change background color if it has changed
-            queue!(stdout, SetBackgroundColor(color.into()))?;
+/// One line of terminal cells, starting at the pixel `row`.
+///
+/// Ends with a reset, so the bold underscores don't carry over to whatever is printed next.
+fn queue_row<W: Write>(
+    out: &mut W,
+    buffer: &DrawBuffer,
+    row: u16,
+    color_depth: ColorDepth,
+) -> Result<()> {
+    let cell_mode = buffer.cell_mode;
+    if cell_mode == CellMode::Underscore {
+        queue!(out, SetAttribute(style::Attribute::Bold))?;
+        queue!(out, SetAttribute(style::Attribute::Underlined))?;
+    }
 
-            if HEIGHT_SCALE >= 2 {
-                let bottom_color = buffer.get_color(column, row + 1);
-                queue!(stdout, SetForegroundColor(bottom_color.into()))?;
-                queue!(stdout, Print("_"))?; // TODO: Print "Lower half block" ▄ (but cmd doesn't always support it)
-            } else {
-                queue!(stdout, Print(" "))?;
+    let (cell_width, cell_height) = cell_mode.pixels();
+    for column in (0..buffer.width).step_by(cell_width.into()) {
+        let (background, foreground, character) = match cell_mode {
+            CellMode::HalfBlock | CellMode::Underscore => (
+                buffer.get_color(column, row),
+                buffer.get_color(column, row + 1),
+                cell_mode.character(0b10),
+            ),
+            CellMode::Quadrant | CellMode::Braille => {
+                let pixels: Vec<RgbColor> = (0..cell_height)
+                    .flat_map(|y| (0..cell_width).map(move |x| (x, y)))
+                    .map(|(x, y)| buffer.get_color(column + x, row + y))
+                    .collect();
+                let (background, foreground, mask) = split_colors(&pixels);
+                (background, foreground, cell_mode.character(mask))
             }
+        };
+
+        // TODO: Only change colors if they have changed
+        queue!(out, SetBackgroundColor(background.to_color(color_depth)))?;
+        queue!(out, SetForegroundColor(foreground.to_color(color_depth)))?;
+        queue!(out, Print(character))?;
+    }
+
+    queue!(out, SetAttribute(style::Attribute::Reset))?;
+    Ok(())
+}
+
+/// Picks the two colors that represent the pixels of a cell best, by splitting them around
+/// the two that are the most different.
+///
+/// Returns the background, the foreground and a bit for every pixel that gets the foreground,
+/// which is never used for more than half of the pixels.
+fn split_colors(pixels: &[RgbColor]) -> (RgbColor, RgbColor, u8) {
+    let mut farthest = (0, 0, 0);
+    for i in 0..pixels.len() {
+        for j in i + 1..pixels.len() {
+            let distance = pixels[i].distance_squared(&pixels[j]);
+            if distance > farthest.2 {
+                farthest = (i, j, distance);
+            }
+        }
+    }
+    let (first, second, distance) = farthest;
+    if distance == 0 {
+        let color = pixels.first().copied().unwrap_or(RgbColor::BLACK);
+        return (color, color, 0);
+    }
+
+    let mut mask = 0u8;
+    for (index, pixel) in pixels.iter().enumerate() {
+        if pixel.distance_squared(&pixels[second]) < pixel.distance_squared(&pixels[first]) {
+            mask |= 1 << index;
         }
     }
+    // The background covers more of the cell, especially with the thin Braille dots
+    if mask.count_ones() as usize * 2 > pixels.len() {
+        mask = !mask & ((1u16 << pixels.len()) - 1) as u8;
+    }
+
+    let average = |foreground: bool| {
+        let group: Vec<RgbColor> = pixels
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| (mask & (1 << index) != 0) == foreground)
+            .map(|(_, pixel)| *pixel)
+            .collect();
+        RgbColor::average(&group)
+    };
+    (average(false), average(true), mask)
+}
+
+/// Draws a line of text over the bottom row of the terminal, for errors and other messages
+pub fn render_status(text: &str) -> Result<()> {
+    let (_, rows) = terminal::size()?;
+    render_line(text, rows.saturating_sub(1), Color::DarkRed)
+}
+
+/// Draws a line of text over the top row of the terminal, for statistics
+pub fn render_debug(text: &str) -> Result<()> {
+    render_line(text, 0, Color::DarkGrey)
+}
+
+fn render_line(text: &str, row: u16, background: Color) -> Result<()> {
+    let mut stdout = stdout();
+    let (columns, _) = terminal::size()?;
+
+    // Cut off at the terminal width, so that it doesn't wrap
+    let line: String = text
+        .lines()
+        .next()
+        .unwrap_or("")
+        .chars()
+        .chain(std::iter::repeat(' '))
+        .take(columns as usize)
+        .collect();
+
+    queue!(
+        stdout,
+        SetAttribute(style::Attribute::Reset),
+        MoveTo(0, row),
+        SetBackgroundColor(background),
+        SetForegroundColor(Color::White),
+        Print(line),
+        ResetColor
+    )?;
 
     stdout.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: &RgbColor) -> (u8, u8, u8) {
+        (color.r, color.g, color.b)
+    }
+
+    #[test]
+    fn sizes_that_overflow_are_rejected() {
+        assert_eq!(CellMode::Braille.pixel_size(80, 24), Some((160, 96)));
+        assert_eq!(CellMode::Braille.pixel_size(20000, 20000), None);
+        assert_eq!(
+            CellMode::HalfBlock.pixel_size(40000, 100),
+            Some((40000, 200))
+        );
+        assert_eq!(CellMode::HalfBlock.pixel_size(100, 40000), None);
+        assert!(DrawBuffer::with_size(20000, 20000, CellMode::Braille).is_none());
+        assert!(DrawBuffer::with_size(20, 10, CellMode::Quadrant).is_some());
+    }
+
+    #[test]
+    fn quadrant_characters() {
+        assert_eq!(CellMode::Quadrant.character(0b0000), ' ');
+        assert_eq!(CellMode::Quadrant.character(0b0001), '▘');
+        assert_eq!(CellMode::Quadrant.character(0b0010), '▝');
+        assert_eq!(CellMode::Quadrant.character(0b0100), '▖');
+        assert_eq!(CellMode::Quadrant.character(0b1000), '▗');
+        assert_eq!(CellMode::Quadrant.character(0b0011), '▀');
+        assert_eq!(CellMode::Quadrant.character(0b1100), '▄');
+        assert_eq!(CellMode::Quadrant.character(0b0101), '▌');
+        assert_eq!(CellMode::Quadrant.character(0b1111), '█');
+    }
+
+    #[test]
+    fn braille_characters() {
+        assert_eq!(CellMode::Braille.character(0), '⠀');
+        // Left column from the top, dots 1, 2, 3 and 7
+        assert_eq!(CellMode::Braille.character(1 << 0), '⠁');
+        assert_eq!(CellMode::Braille.character(1 << 2), '⠂');
+        assert_eq!(CellMode::Braille.character(1 << 4), '⠄');
+        assert_eq!(CellMode::Braille.character(1 << 6), '⡀');
+        // Right column from the top, dots 4, 5, 6 and 8
+        assert_eq!(CellMode::Braille.character(1 << 1), '⠈');
+        assert_eq!(CellMode::Braille.character(1 << 3), '⠐');
+        assert_eq!(CellMode::Braille.character(1 << 5), '⠠');
+        assert_eq!(CellMode::Braille.character(1 << 7), '⢀');
+        assert_eq!(CellMode::Braille.character(0xff), '⣿');
+    }
+
+    #[test]
+    fn uniform_cell_has_no_foreground() {
+        let red = RgbColor::new(255, 0, 0);
+        let (background, foreground, mask) = split_colors(&[red; 4]);
+        assert_eq!(rgb(&background), (255, 0, 0));
+        assert_eq!(rgb(&foreground), (255, 0, 0));
+        assert_eq!(mask, 0);
+    }
+
+    #[test]
+    fn foreground_is_the_minority() {
+        let white = RgbColor::new(255, 255, 255);
+        let (background, foreground, mask) = split_colors(&[white, RgbColor::BLACK, white, white]);
+        assert_eq!(rgb(&background), (255, 255, 255));
+        assert_eq!(rgb(&foreground), (0, 0, 0));
+        assert_eq!(mask, 0b0010);
+    }
+
+    #[test]
+    fn groups_are_averaged() {
+        let pixels = [
+            RgbColor::new(250, 0, 0),
+            RgbColor::new(240, 0, 0),
+            RgbColor::new(0, 0, 250),
+            RgbColor::new(0, 0, 230),
+            RgbColor::new(200, 0, 0),
+            RgbColor::new(210, 0, 0),
+            RgbColor::new(220, 0, 0),
+            RgbColor::new(230, 0, 0),
+        ];
+        let (background, foreground, mask) = split_colors(&pixels);
+        assert_eq!(rgb(&background), (225, 0, 0));
+        assert_eq!(rgb(&foreground), (0, 0, 240));
+        assert_eq!(mask, 0b0000_1100);
+    }
+
+    /// Whether bold or underlined is still on at the end of the escape sequences in `output`
+    fn attributes_left_on(output: &str) -> bool {
+        let mut on = false;
+        for code in output.split("\x1b[").skip(1) {
+            match code.split('m').next() {
+                Some("1") | Some("4") => on = true,
+                Some("0") => on = false,
+                _ => (),
+            }
+        }
+        on
+    }
+
+    #[test]
+    fn every_row_resets_the_attributes() {
+        let mut buffer = DrawBuffer::with_size(4, 3, CellMode::Underscore).unwrap();
+        let mut output = Vec::new();
+        queue_frame(&mut output, &buffer, ColorDepth::TrueColor).unwrap();
+        let underscores = String::from_utf8(output).unwrap();
+        assert!(underscores.contains("\x1b[1m") && underscores.contains("\x1b[4m"));
+        for row in 2..=3 {
+            assert!(underscores.contains(&format!("\x1b[0m\x1b[{};1H", row)));
+        }
+        assert!(underscores.ends_with("\x1b[0m"));
+        assert!(!attributes_left_on(&underscores));
+
+        buffer.cell_mode = CellMode::HalfBlock;
+        let mut output = Vec::new();
+        queue_frame(&mut output, &buffer, ColorDepth::TrueColor).unwrap();
+        let half_blocks = String::from_utf8(output).unwrap();
+        assert!(!attributes_left_on(&(underscores + &half_blocks)));
+
+        let mut output = Vec::new();
+        buffer.cell_mode = CellMode::Underscore;
+        write_ansi(&mut output, &buffer, ColorDepth::Ansi16).unwrap();
+        let lines = String::from_utf8(output).unwrap();
+        assert_eq!(lines.lines().count(), 3);
+        assert!(lines.lines().all(|line| line.ends_with("\x1b[0m")));
+    }
+}
@@ -0,0 +1,32 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A fresh temporary directory for the files of one test, removed again when it's dropped
+pub struct TestDirectory {
+    path: PathBuf,
+}
+
+impl TestDirectory {
+    /// Tests run in parallel, so `name` has to be unique among them
+    pub fn new(name: &str) -> TestDirectory {
+        let path = std::env::temp_dir().join(format!("rust-cube-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TestDirectory { path }
+    }
+}
+
+impl Deref for TestDirectory {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,359 @@
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+
+use crate::terminal_renderer::RgbColor;
+use crate::vector2::Vector2;
+
+/// What happens with texture coordinates outside of the `0` to `1` range
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum AddressMode {
+    Repeat,
+    ClampToEdge,
+    MirroredRepeat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FilterMode {
+    Nearest,
+    /// Bilinear filtering between the four closest texels
+    Linear,
+}
+
+/// How the mip level is chosen when a texture is minified
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MipmapMode {
+    /// Always use the full resolution image
+    None,
+    Nearest,
+    /// Trilinear filtering, blends between the two closest levels
+    Linear,
+}
+
+#[derive(Copy, Clone)]
+pub struct Sampler {
+    /// For the horizontal texture coordinate
+    pub address_mode_u: AddressMode,
+    /// For the vertical texture coordinate
+    pub address_mode_v: AddressMode,
+    pub filter: FilterMode,
+    pub mipmap_mode: MipmapMode,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            filter: FilterMode::Linear,
+            mipmap_mode: MipmapMode::Linear,
+        }
+    }
+}
+
+pub struct Texture {
+    /// Mip chain, starting with the full resolution image and halving the size down to 1x1
+    pub levels: Vec<RgbImage>,
+}
+
+impl Texture {
+    pub fn new(image: DynamicImage) -> Texture {
+        let mut levels = vec![image.to_rgb8()];
+        loop {
+            let previous = &levels[levels.len() - 1];
+            if previous.width() <= 1 && previous.height() <= 1 {
+                break;
+            }
+
+            let width = (previous.width() / 2).max(1);
+            let height = (previous.height() / 2).max(1);
+            levels.push(imageops::resize(
+                previous,
+                width,
+                height,
+                imageops::FilterType::Triangle,
+            ));
+        }
+
+        Texture { levels }
+    }
+
+    /// Magenta and dark gray squares, shown instead of textures that can't be loaded
+    pub fn checkerboard(size: u32, squares: u32) -> Texture {
+        let square_size = (size / squares.max(1)).max(1);
+        let image = RgbImage::from_fn(size, size, |x, y| {
+            if (x / square_size + y / square_size).is_multiple_of(2) {
+                Rgb([255, 0, 255])
+            } else {
+                Rgb([40, 40, 40])
+            }
+        });
+        Texture::new(DynamicImage::ImageRgb8(image))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.levels[0].width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.levels[0].height()
+    }
+
+    /// Texel lookup, the coordinates can be outside of the image
+    fn get_texel(&self, level: usize, sampler: &Sampler, x: i64, y: i64) -> [f64; 3] {
+        let image = &self.levels[level];
+        let x = apply_address_mode(sampler.address_mode_u, x, image.width());
+        let y = apply_address_mode(sampler.address_mode_v, y, image.height());
+        let pixel = image.get_pixel(x, y);
+        [pixel.0[0] as f64, pixel.0[1] as f64, pixel.0[2] as f64]
+    }
+}
+
+fn apply_address_mode(address_mode: AddressMode, coordinate: i64, size: u32) -> u32 {
+    let size = size.max(1) as i64;
+    let coordinate = match address_mode {
+        AddressMode::Repeat => coordinate.rem_euclid(size),
+        AddressMode::ClampToEdge => coordinate.clamp(0, size - 1),
+        AddressMode::MirroredRepeat => {
+            let coordinate = coordinate.rem_euclid(2 * size);
+            if coordinate >= size {
+                2 * size - 1 - coordinate
+            } else {
+                coordinate
+            }
+        }
+    };
+    coordinate as u32
+}
+
+impl Sampler {
+    /// `uv` goes from `(0,0)` at the top left to `(1,1)` at the bottom right
+    pub fn sample(&self, texture: &Texture, uv: &Vector2<f64>) -> RgbColor {
+        to_color(self.sample_level(texture, 0, uv))
+    }
+
+    /// Like `sample`, but picks the mip level from how much `uv` changes between neighbouring pixels
+    pub fn sample_grad(
+        &self,
+        texture: &Texture,
+        uv: &Vector2<f64>,
+        uv_dx: &Vector2<f64>,
+        uv_dy: &Vector2<f64>,
+    ) -> RgbColor {
+        let size = Vector2::new(texture.width() as f64, texture.height() as f64);
+        let texels_per_pixel = (*uv_dx * size).length().max((*uv_dy * size).length());
+        let level_of_detail = texels_per_pixel.log2();
+        let max_level = (texture.levels.len() - 1) as f64;
+
+        if !level_of_detail.is_finite() || level_of_detail <= 0. {
+            return self.sample(texture, uv);
+        }
+        let level_of_detail = level_of_detail.min(max_level);
+
+        let color = match self.mipmap_mode {
+            MipmapMode::None => self.sample_level(texture, 0, uv),
+            MipmapMode::Nearest => self.sample_level(texture, level_of_detail.round() as usize, uv),
+            MipmapMode::Linear => {
+                let level = level_of_detail.floor();
+                let fraction = level_of_detail - level;
+                let level = level as usize;
+                let lower = self.sample_level(texture, level, uv);
+                if fraction <= 0. {
+                    lower
+                } else {
+                    let upper = self.sample_level(texture, level + 1, uv);
+                    lerp(&lower, &upper, fraction)
+                }
+            }
+        };
+
+        to_color(color)
+    }
+
+    fn sample_level(&self, texture: &Texture, level: usize, uv: &Vector2<f64>) -> [f64; 3] {
+        let image = &texture.levels[level];
+        let x = uv.x * image.width() as f64;
+        let y = uv.y * image.height() as f64;
+
+        match self.filter {
+            FilterMode::Nearest => {
+                texture.get_texel(level, self, x.floor() as i64, y.floor() as i64)
+            }
+            FilterMode::Linear => {
+                // Texel centers are at half coordinates
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let (fraction_x, fraction_y) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let texel = |x, y| texture.get_texel(level, self, x, y);
+                let top = lerp(&texel(x0, y0), &texel(x0 + 1, y0), fraction_x);
+                let bottom = lerp(&texel(x0, y0 + 1), &texel(x0 + 1, y0 + 1), fraction_x);
+                lerp(&top, &bottom, fraction_y)
+            }
+        }
+    }
+}
+
+fn lerp(a: &[f64; 3], b: &[f64; 3], t: f64) -> [f64; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+fn to_color(color: [f64; 3]) -> RgbColor {
+    RgbColor {
+        r: color[0].round() as u8,
+        g: color[1].round() as u8,
+        b: color[2].round() as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeat_wraps_around() {
+        let map = |coordinate| apply_address_mode(AddressMode::Repeat, coordinate, 4);
+        assert_eq!([-5, -1, 0, 3, 4, 9].map(map), [3, 3, 0, 3, 0, 1]);
+    }
+
+    #[test]
+    fn clamp_stops_at_the_edges() {
+        let map = |coordinate| apply_address_mode(AddressMode::ClampToEdge, coordinate, 4);
+        assert_eq!([-100, -1, 0, 3, 4, 100].map(map), [0, 0, 0, 3, 3, 3]);
+    }
+
+    #[test]
+    fn mirrored_repeat_reflects_at_the_edges() {
+        let map = |coordinate| apply_address_mode(AddressMode::MirroredRepeat, coordinate, 4);
+        assert_eq!(
+            [-5, -4, -1, 0, 3, 4, 5, 7, 8].map(map),
+            [3, 3, 0, 0, 3, 3, 2, 0, 0]
+        );
+    }
+
+    #[test]
+    fn empty_size_is_the_first_texel() {
+        for address_mode in [
+            AddressMode::Repeat,
+            AddressMode::ClampToEdge,
+            AddressMode::MirroredRepeat,
+        ] {
+            assert_eq!(apply_address_mode(address_mode, -3, 0), 0);
+        }
+    }
+
+    #[test]
+    fn sampling_outside_uses_the_address_mode() {
+        // Black on the left, white on the right
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 255; 3]));
+        let texture = Texture::new(DynamicImage::ImageRgb8(image));
+        let sample = |address_mode_u, u| {
+            let sampler = Sampler {
+                address_mode_u,
+                filter: FilterMode::Nearest,
+                ..Sampler::default()
+            };
+            sampler.sample(&texture, &Vector2::new(u, 0.5)).r
+        };
+
+        assert_eq!(sample(AddressMode::Repeat, -0.25), 255);
+        assert_eq!(sample(AddressMode::ClampToEdge, -0.25), 0);
+        assert_eq!(sample(AddressMode::MirroredRepeat, -0.25), 0);
+        assert_eq!(sample(AddressMode::Repeat, 1.25), 0);
+        assert_eq!(sample(AddressMode::ClampToEdge, 1.25), 255);
+        assert_eq!(sample(AddressMode::MirroredRepeat, 1.25), 255);
+    }
+
+    #[test]
+    fn bilinear_filter_blends_neighbours() {
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 200; 3]));
+        let texture = Texture::new(DynamicImage::ImageRgb8(image));
+        let sampler = Sampler {
+            address_mode_u: AddressMode::ClampToEdge,
+            ..Sampler::default()
+        };
+        // Between the two texel centers
+        assert_eq!(sampler.sample(&texture, &Vector2::new(0.5, 0.5)).r, 100);
+        assert_eq!(sampler.sample(&texture, &Vector2::new(0.25, 0.5)).r, 0);
+        assert_eq!(sampler.sample(&texture, &Vector2::new(0.375, 0.5)).r, 50);
+    }
+
+    /// A red 4x4 level, a green 2x2 level and a blue 1x1 level
+    fn mip_chain() -> Texture {
+        let level = |size, color| RgbImage::from_pixel(size, size, Rgb(color));
+        Texture {
+            levels: vec![
+                level(4, [255, 0, 0]),
+                level(2, [0, 255, 0]),
+                level(1, [0, 0, 255]),
+            ],
+        }
+    }
+
+    /// Samples the mip chain with `texels` texels of the full resolution image per pixel
+    fn sample_mip(mipmap_mode: MipmapMode, texels: f64) -> (u8, u8, u8) {
+        let sampler = Sampler {
+            mipmap_mode,
+            ..Sampler::default()
+        };
+        let color = sampler.sample_grad(
+            &mip_chain(),
+            &Vector2::new(0.5, 0.5),
+            &Vector2::new(texels / 4., 0.),
+            &Vector2::new(0., texels / 8.),
+        );
+        (color.r, color.g, color.b)
+    }
+
+    #[test]
+    fn mip_level_follows_the_derivatives() {
+        assert_eq!(sample_mip(MipmapMode::Nearest, 0.5), (255, 0, 0));
+        assert_eq!(sample_mip(MipmapMode::Nearest, 1.), (255, 0, 0));
+        assert_eq!(sample_mip(MipmapMode::Nearest, 2.), (0, 255, 0));
+        assert_eq!(sample_mip(MipmapMode::Nearest, 4.), (0, 0, 255));
+        // Clamped to the smallest level
+        assert_eq!(sample_mip(MipmapMode::Nearest, 64.), (0, 0, 255));
+    }
+
+    #[test]
+    fn larger_derivative_picks_the_level() {
+        let sampler = Sampler {
+            mipmap_mode: MipmapMode::Nearest,
+            ..Sampler::default()
+        };
+        let color = sampler.sample_grad(
+            &mip_chain(),
+            &Vector2::new(0.5, 0.5),
+            &Vector2::new(0., 0.),
+            &Vector2::new(0., 0.5),
+        );
+        assert_eq!((color.r, color.g, color.b), (0, 255, 0));
+    }
+
+    #[test]
+    fn no_mipmaps_uses_the_full_image() {
+        assert_eq!(sample_mip(MipmapMode::None, 4.), (255, 0, 0));
+    }
+
+    #[test]
+    fn trilinear_blends_between_levels() {
+        // Halfway between levels, up to rounding
+        let assert_close = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            assert!(
+                a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1 && a.2.abs_diff(b.2) <= 1,
+                "{:?} != {:?}",
+                a,
+                b
+            );
+        };
+        let sqrt_2 = 2f64.sqrt();
+        assert_close(sample_mip(MipmapMode::Linear, sqrt_2), (128, 128, 0));
+        assert_close(sample_mip(MipmapMode::Linear, 2. * sqrt_2), (0, 128, 128));
+        assert_close(sample_mip(MipmapMode::Linear, 2.), (0, 255, 0));
+    }
+}
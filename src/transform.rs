@@ -0,0 +1,37 @@
+use crate::matrix4::Matrix4;
+use crate::quaternion::Quaternion;
+use crate::vector3::Vector3;
+
+/// Scales, then rotates, then translates
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub position: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            position: Vector3::zero(),
+            rotation: Quaternion::identity(),
+            scale: Vector3::one(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn to_matrix(&self) -> Matrix4 {
+        Matrix4::translation(&self.position)
+            * self.rotation.to_matrix()
+            * Matrix4::scale(&self.scale)
+    }
+
+    /// Matrix that undoes `to_matrix`, for example to turn a camera transform into a view matrix
+    pub fn to_inverse_matrix(&self) -> Matrix4 {
+        let inverse_scale = Vector3::new(1. / self.scale.x, 1. / self.scale.y, 1. / self.scale.z);
+        Matrix4::scale(&inverse_scale)
+            * self.rotation.conjugate().to_matrix()
+            * Matrix4::translation(&(self.position * -1.))
+    }
+}
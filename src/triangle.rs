@@ -0,0 +1,189 @@
+use crate::clipping::{self, ClipVertex};
+use crate::material::{Material, Shading};
+use crate::rasterizer::{self, Fragment};
+use crate::render_context::RenderContext;
+use crate::terminal_renderer::{DrawBuffer, RgbColor};
+use crate::vector2::Vector2;
+use crate::vector3::Vector3;
+use crate::vector4::Vector4;
+
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    pub position: Vector3,
+    pub uv: Vector2<f64>,
+    /// Multiplied with the material
+    pub color: RgbColor,
+    /// Replaces `color` in the shadows
+    pub shadow_color: RgbColor,
+    /// Added to the material, for highlights
+    pub specular: RgbColor,
+    /// Unit length, only used for `Shading::Phong`
+    pub normal: Vector3,
+}
+
+pub struct Triangle3D {
+    pub vertices: [Vertex; 3],
+}
+
+impl Triangle3D {
+    pub fn scaled_normal(&self) -> Vector3 {
+        let [a, b, c] = &self.vertices;
+        (c.position - a.position).cross(&(b.position - a.position))
+    }
+
+    /// Unit length, pointing away from the front side
+    pub fn normal(&self) -> Vector3 {
+        (self.scaled_normal() * -1.).normalized()
+    }
+
+    pub fn center(&self) -> Vector3 {
+        let [a, b, c] = &self.vertices;
+        (a.position + b.position + c.position) * (1. / 3.)
+    }
+
+    pub fn render(
+        &self,
+        draw_buffer: &mut DrawBuffer,
+        context: &RenderContext,
+        material: &Material,
+    ) {
+        let view_projection = context.camera.view_projection_matrix();
+
+        let clip = self
+            .vertices
+            .map(|v| view_projection.transform(&Vector4::from_point(&v.position)));
+        let polygon = clipping::clip_triangle(&clip);
+        if polygon.is_empty() {
+            return;
+        }
+
+        let uvs = self.vertices.map(|v| v.uv);
+        let colors = self.vertices.map(|v| v.color);
+        let shadow_colors = self.vertices.map(|v| v.shadow_color);
+        let speculars = self.vertices.map(|v| v.specular);
+        let positions = self.vertices.map(|v| v.position);
+        let normals = self.vertices.map(|v| v.normal);
+        let shading = context.shading(material);
+        let per_pixel_lighting = shading == Shading::Phong;
+
+        let uv_at = |weights: &[f64; 3]| {
+            uvs[0] * Vector2::new(weights[0], weights[0])
+                + uvs[1] * Vector2::new(weights[1], weights[1])
+                + uvs[2] * Vector2::new(weights[2], weights[2])
+        };
+        let interpolate_color = |colors: &[RgbColor; 3], weights: &[f64; 3]| {
+            let channel = |get: fn(&RgbColor) -> u8| {
+                (get(&colors[0]) as f64 * weights[0]
+                    + get(&colors[1]) as f64 * weights[1]
+                    + get(&colors[2]) as f64 * weights[2])
+                    .round()
+                    .clamp(0., 255.) as u8
+            };
+            RgbColor::new(channel(|c| c.r), channel(|c| c.g), channel(|c| c.b))
+        };
+        let interpolate_vector = |vectors: &[Vector3; 3], weights: &[f64; 3]| {
+            vectors[0] * weights[0] + vectors[1] * weights[1] + vectors[2] * weights[2]
+        };
+
+        // The clipped polygon is convex, so it can be drawn as a fan
+        for i in 1..polygon.len() - 1 {
+            let corners = [polygon[0], polygon[i], polygon[i + 1]];
+            rasterize_clipped(
+                draw_buffer,
+                &corners,
+                |draw_buffer, fragment, weights, uv_dx, uv_dy| {
+                    let uv = uv_at(weights);
+                    let uv_dx = uv_at(uv_dx) - uv;
+                    let uv_dy = uv_at(uv_dy) - uv;
+
+                    let position = interpolate_vector(&positions, weights);
+                    let normal = interpolate_vector(&normals, weights).normalized();
+                    let (color, shadow_color, specular) = if per_pixel_lighting {
+                        let lighting = context.light(material, &position, &normal);
+                        let color = interpolate_color(&colors, weights);
+                        (
+                            color.multiply(&lighting.diffuse),
+                            color.multiply(&lighting.shadowed_diffuse),
+                            lighting.specular,
+                        )
+                    } else {
+                        (
+                            interpolate_color(&colors, weights),
+                            interpolate_color(&shadow_colors, weights),
+                            interpolate_color(&speculars, weights),
+                        )
+                    };
+
+                    // Shadows are always per pixel, even for flat shading
+                    let (color, specular) = if shading == Shading::Unlit {
+                        (color, specular)
+                    } else {
+                        let visibility = context.shadow_visibility(&position, &normal);
+                        (
+                            shadow_color.lerp(&color, visibility),
+                            RgbColor::BLACK.lerp(&specular, visibility),
+                        )
+                    };
+
+                    draw_buffer.set_color(
+                        fragment.column,
+                        fragment.row,
+                        &context.shade(material, &uv, &uv_dx, &uv_dy, &color, &specular),
+                    );
+                },
+            );
+        }
+    }
+}
+
+/// Rasterizes part of a triangle and depth tests it, `shade` gets the weights of the original vertices
+/// at the pixel and at its right and lower neighbours
+fn rasterize_clipped<F>(draw_buffer: &mut DrawBuffer, corners: &[ClipVertex; 3], mut shade: F)
+where
+    F: FnMut(&mut DrawBuffer, &Fragment, &[f64; 3], &[f64; 3], &[f64; 3]),
+{
+    // Viewport: -1 to 1 range, with (0,0) at the center
+    // Transform to draw buffer size
+    let half_width = 0.5 * draw_buffer.width as f64;
+    let half_height = 0.5 * draw_buffer.height as f64;
+    let projected = corners.map(|c| c.position.perspective_divide());
+    let screen = projected.map(|v| {
+        Vector2::new(
+            v.x * half_width + half_width,
+            v.y * half_height + half_height,
+        )
+    });
+    let inverse_w = corners.map(|c| 1. / c.position.w);
+
+    // Perspective correct interpolation, weights each corner by its 1/w
+    let weights_at = |barycentric: &[f64; 3]| {
+        let weighted = [0, 1, 2].map(|i| barycentric[i] * inverse_w[i]);
+        let sum = weighted[0] + weighted[1] + weighted[2];
+        [0, 1, 2].map(|vertex| {
+            (0..3)
+                .map(|i| corners[i].weights[vertex] * weighted[i] / sum)
+                .sum::<f64>()
+        })
+    };
+
+    let (width, height) = (draw_buffer.width, draw_buffer.height);
+    rasterizer::rasterize_triangle(&screen, width, height, |fragment: Fragment| {
+        let b = &fragment.barycentric;
+
+        // The projected depth is linear in screen space
+        let depth = projected[0].z * b[0] + projected[1].z * b[1] + projected[2].z * b[2];
+        if !draw_buffer.depth_test(fragment.column, fragment.row, depth) {
+            return;
+        }
+
+        // Neighbouring cells, for picking the mip level
+        let (dx, dy) = (&fragment.barycentric_dx, &fragment.barycentric_dy);
+        shade(
+            draw_buffer,
+            &fragment,
+            &weights_at(b),
+            &weights_at(&[0, 1, 2].map(|i| b[i] + dx[i])),
+            &weights_at(&[0, 1, 2].map(|i| b[i] + dy[i])),
+        );
+    });
+}
@@ -64,11 +64,11 @@ impl Vector2<f64> {
     }
 }
 
-impl Into<Vector2<f64>> for Vector3 {
-    fn into(self) -> Vector2<f64> {
+impl From<Vector3> for Vector2<f64> {
+    fn from(item: Vector3) -> Self {
         Vector2 {
-            x: self.x,
-            y: self.y,
+            x: item.x,
+            y: item.y,
         }
     }
 }
@@ -22,6 +22,19 @@ impl Vector3 {
         self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
     }
 
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Vector3 {
+        let length_squared = self.dot(self);
+        if length_squared > f64::EPSILON {
+            *self * (1. / length_squared.sqrt())
+        } else {
+            Vector3::zero()
+        }
+    }
+
     pub fn zero() -> Vector3 {
         Vector3 {
             x: 0.,
@@ -40,35 +53,37 @@ impl Vector3 {
 
     /// Rotates in the X-Y-Z order around the origin
     pub fn rotate_euler(&self, euler_angles: &Vector3) -> Vector3 {
-        fn to_rad(v: f64) -> f64 {
-            v * std::f64::consts::PI / 180.
-        }
-
         let rad = Vector3::new(
-            to_rad(euler_angles.x),
-            to_rad(euler_angles.y),
-            to_rad(euler_angles.z),
+            euler_angles.x.to_radians(),
+            euler_angles.y.to_radians(),
+            euler_angles.z.to_radians(),
         );
 
-        let result_x = Vector3 {
+        self.rotate_x(rad.x).rotate_y(rad.y).rotate_z(rad.z)
+    }
+
+    fn rotate_x(&self, rad: f64) -> Vector3 {
+        Vector3 {
             x: self.x,
-            y: self.y * rad.x.cos() - self.z * rad.x.sin(),
-            z: self.z * rad.x.cos() + self.y * rad.x.sin(),
-        };
-
-        let result_xy = Vector3 {
-            x: result_x.x * rad.y.cos() + result_x.z * rad.y.sin(),
-            y: result_x.y,
-            z: result_x.z * rad.y.cos() - result_x.x * rad.y.sin(),
-        };
-
-        let result_xyz = Vector3 {
-            x: result_xy.x * rad.z.cos() - result_xy.y * rad.z.sin(),
-            y: result_xy.y * rad.z.cos() + result_xy.x * rad.z.sin(),
-            z: result_xy.z,
-        };
-
-        result_xyz
+            y: self.y * rad.cos() - self.z * rad.sin(),
+            z: self.z * rad.cos() + self.y * rad.sin(),
+        }
+    }
+
+    fn rotate_y(&self, rad: f64) -> Vector3 {
+        Vector3 {
+            x: self.x * rad.cos() + self.z * rad.sin(),
+            y: self.y,
+            z: self.z * rad.cos() - self.x * rad.sin(),
+        }
+    }
+
+    fn rotate_z(&self, rad: f64) -> Vector3 {
+        Vector3 {
+            x: self.x * rad.cos() - self.y * rad.sin(),
+            y: self.y * rad.cos() + self.x * rad.sin(),
+            z: self.z,
+        }
     }
 }
 
@@ -80,6 +95,14 @@ impl std::ops::Mul for Vector3 {
     }
 }
 
+impl std::ops::Mul<f64> for Vector3 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Vector3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
 impl std::ops::Add for Vector3 {
     type Output = Self;
 
@@ -0,0 +1,72 @@
+use crate::vector3::Vector3;
+
+/// Homogeneous coordinates
+#[derive(Copy, Clone)]
+pub struct Vector4 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Vector4 {
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Vector4 {
+        Vector4 { x, y, z, w }
+    }
+
+    pub fn from_point(point: &Vector3) -> Vector4 {
+        Vector4::new(point.x, point.y, point.z, 1.)
+    }
+
+    pub fn from_vector(vector: &Vector3) -> Vector4 {
+        Vector4::new(vector.x, vector.y, vector.z, 0.)
+    }
+
+    pub fn dot(&self, rhs: &Vector4) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn perspective_divide(&self) -> Vector3 {
+        Vector3::new(self.x / self.w, self.y / self.w, self.z / self.w)
+    }
+}
+
+impl From<Vector4> for Vector3 {
+    fn from(item: Vector4) -> Self {
+        Vector3::new(item.x, item.y, item.z)
+    }
+}
+
+impl std::ops::Mul<f64> for Vector4 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Vector4::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+impl std::ops::Add for Vector4 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Vector4::new(
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+            self.w + rhs.w,
+        )
+    }
+}
+
+impl std::ops::Sub for Vector4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Vector4::new(
+            self.x - rhs.x,
+            self.y - rhs.y,
+            self.z - rhs.z,
+            self.w - rhs.w,
+        )
+    }
+}